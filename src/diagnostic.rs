@@ -0,0 +1,297 @@
+use crate::span::Span;
+use std::ops::{Deref, DerefMut};
+
+// Implemented by the error enum of each stage (LexError, ParseError,
+// TypeError and RuntimeError). The Display impl provides the message.
+pub trait ErrorCode: std::fmt::Display {
+    fn code(&self) -> &'static str;
+}
+
+// A secondary location related to a diagnostic, such as the declaration
+// of a function that was called with the wrong arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+// An error from any stage, together with where it happened and any extra
+// labels and notes that help explain it. The contents are boxed, so a
+// Result carrying one stays small on the error side, which keeps the frames
+// of the recursive parser, checker and interpreter small too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic<E>(Box<DiagnosticData<E>>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticData<E> {
+    pub error: E,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl<E> Deref for Diagnostic<E> {
+    type Target = DiagnosticData<E>;
+
+    fn deref(&self) -> &DiagnosticData<E> {
+        &self.0
+    }
+}
+
+impl<E> DerefMut for Diagnostic<E> {
+    fn deref_mut(&mut self) -> &mut DiagnosticData<E> {
+        &mut self.0
+    }
+}
+
+impl<E: ErrorCode> Diagnostic<E> {
+    pub fn new(error: E, span: Span) -> Self {
+        Diagnostic(Box::new(DiagnosticData {
+            error,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }))
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.error.code()
+    }
+
+    pub fn message(&self) -> String {
+        self.error.to_string()
+    }
+
+    // Renders the diagnostic the way rustc does: the message, the location,
+    // then every labelled source line with the span underlined. The primary
+    // span is marked with '^' and secondary labels with '-'.
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let mut annotations = vec![(self.span, '^', "")];
+        for label in &self.labels {
+            annotations.push((label.span, '-', label.message.as_str()));
+        }
+        annotations.sort_by_key(|(span, _, _)| span.start);
+
+        let width = annotations
+            .iter()
+            .map(|(span, _, _)| span.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        let mut output = format!("error[{}]: {}\n", self.code(), self.message());
+        output.push_str(&format!("{}--> {}:{}\n", gutter, file_name, self.span));
+        output.push_str(&format!("{} |\n", gutter));
+
+        let mut previous_line = None;
+        for (span, marker, message) in annotations {
+            let (text, start) = source_line(source, span);
+
+            if previous_line != Some(span.line) {
+                match previous_line {
+                    // A single skipped line is cheaper to show than to elide
+                    Some(line) if span.line == line + 2 => {
+                        let skipped = source.lines().nth(line).unwrap_or("");
+                        output.push_str(&format!("{:>width$} | {}\n", line + 1, expand_tabs(skipped)));
+                    },
+                    Some(line) if span.line > line + 2 => output.push_str("...\n"),
+                    _ => {},
+                }
+                output.push_str(&format!("{:>width$} | {}\n", span.line, expand_tabs(text)));
+            }
+
+            // Only the first line of a span that covers several lines is underlined
+            let offset = span.start.saturating_sub(start).min(text.len());
+            let end = span.end.saturating_sub(start).clamp(offset, text.trim_end().len().max(offset));
+            let padding = " ".repeat(expand_tabs(&text[..offset]).chars().count());
+            let length = expand_tabs(&text[offset..end]).chars().count().max(1);
+
+            output.push_str(&format!(
+                "{} | {}{}",
+                gutter,
+                padding,
+                marker.to_string().repeat(length)
+            ));
+            if !message.is_empty() {
+                output.push_str(&format!(" {}", message));
+            }
+            output.push('\n');
+
+            previous_line = Some(span.line);
+        }
+
+        for note in &self.notes {
+            output.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+
+        output.trim_end().to_string()
+    }
+}
+
+impl<E: ErrorCode> std::fmt::Display for Diagnostic<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message(), self.span)
+    }
+}
+
+// Renders several diagnostics one after another, with a summary line when
+// there is more than one
+pub fn render_all<E: ErrorCode>(diagnostics: &[Diagnostic<E>], file_name: &str, source: &str) -> String {
+    let mut output = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(file_name, source))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if diagnostics.len() > 1 {
+        output.push_str(&format!("\n\nerror: aborting due to {} previous errors", diagnostics.len()));
+    }
+
+    output
+}
+
+// Returns the source line containing the start of `span`, without its line
+// ending, together with the byte offset that line starts at
+fn source_line(source: &str, span: Span) -> (&str, usize) {
+    let position = span.start.min(source.len());
+    let start = source[..position].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = source[start..].find('\n').map(|i| start + i).unwrap_or(source.len());
+
+    (source[start..end].trim_end_matches('\r'), start)
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', "    ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Broken;
+
+    impl std::fmt::Display for Broken {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "something broke")
+        }
+    }
+
+    impl ErrorCode for Broken {
+        fn code(&self) -> &'static str {
+            "E0000"
+        }
+    }
+
+    // The span of the first occurrence of `text` in `source`
+    fn span_of(source: &str, text: &str) -> Span {
+        let start = source.find(text).expect("text is in the source");
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        Span::new(
+            start,
+            start + text.len(),
+            source[..start].matches('\n').count() + 1,
+            source[line_start..start].chars().count() + 1,
+        )
+    }
+
+    #[test]
+    fn single_line_span() {
+        let source = "let x = 5 + y\n";
+        let rendered = Diagnostic::new(Broken, span_of(source, "y")).render("test", source);
+
+        let expected = [
+            "error[E0000]: something broke",
+            " --> test:1:13",
+            "  |",
+            "1 | let x = 5 + y",
+            "  |             ^",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn label_on_another_line() {
+        let source = "let x = 5\nlet y = 6\nx = True\n";
+        let rendered = Diagnostic::new(Broken, span_of(source, "True"))
+            .with_label(span_of(source, "let x = 5"), "declared here")
+            .with_note("check the type")
+            .render("test", source);
+
+        // The line in between is short enough to show rather than elide
+        let expected = [
+            "error[E0000]: something broke",
+            " --> test:3:5",
+            "  |",
+            "1 | let x = 5",
+            "  | --------- declared here",
+            "2 | let y = 6",
+            "3 | x = True",
+            "  |     ^^^^",
+            "  = note: check the type",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn distant_lines_are_elided() {
+        let source = "let x = 5\n\n\n\n\n\n\n\n\nx = True\n";
+        let rendered = Diagnostic::new(Broken, span_of(source, "True"))
+            .with_label(span_of(source, "x"), "declared here")
+            .render("test", source);
+
+        let expected = [
+            "error[E0000]: something broke",
+            "  --> test:10:5",
+            "   |",
+            " 1 | let x = 5",
+            "   |     - declared here",
+            "...",
+            "10 | x = True",
+            "   |     ^^^^",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn multi_line_span_underlines_its_first_line() {
+        let source = "print(1 +\n    2)\n";
+        let rendered = Diagnostic::new(Broken, span_of(source, "print(1 +\n    2)")).render("test", source);
+
+        let expected = [
+            "error[E0000]: something broke",
+            " --> test:1:1",
+            "  |",
+            "1 | print(1 +",
+            "  | ^^^^^^^^^",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn tabs_are_expanded_before_underlining() {
+        let source = "\tlet x = y\n";
+        let rendered = Diagnostic::new(Broken, span_of(source, "y")).render("test", source);
+
+        let expected = [
+            "error[E0000]: something broke",
+            " --> test:1:10",
+            "  |",
+            "1 |     let x = y",
+            "  |             ^",
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+}
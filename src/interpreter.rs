@@ -1,16 +1,54 @@
-use crate::parser::{Expression, Program, Statement, Parameter};
-use std::collections::HashMap;
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::parser::{Expression, ExpressionKind, Program, Statement, StatementKind, Parameter};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+// The list and map built-ins with their number of parameters
+pub const BUILTINS: [(&str, usize); 9] = [
+    ("length", 1),
+    ("push", 2),
+    ("slice", 3),
+    ("get", 2),
+    ("has", 2),
+    ("insert", 3),
+    ("remove", 2),
+    ("keys", 1),
+    ("values", 1),
+];
+
+// How many calls deep a program may go. Each call uses a good deal of the
+// native stack, so running out is reported well before the stack does.
+pub const MAX_CALL_DEPTH: usize = 1000;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     String(String),
     Integer(i64),
     Boolean(bool),
+    List(Vec<Value>),
+    Map(BTreeMap<MapKey, Value>),
+    // Fields are kept in the order the record type declares them
+    Record {
+        type_name: String,
+        fields: Vec<(String, Value)>,
+    },
+    // A variant of an enum, with its payload in declaration order
+    Variant {
+        type_name: String,
+        variant: String,
+        fields: Vec<(String, Value)>,
+    },
     Null,
+    // print and the list and map built-ins, run by `call_builtin`
+    Builtin(String),
+    // A call runs in a scope nested inside `closure`, the environment the
+    // function was declared in, rather than inside the caller's
     Function {
         name: String,
         parameters: Vec<Parameter>,
         body: Vec<Statement>,
+        closure: Rc<RefCell<Environment>>,
     },
 }
 
@@ -20,16 +58,236 @@ impl std::fmt::Display for Value {
             Value::String(s) => write!(f, "{}", s),
             Value::Integer(i) => write!(f, "{}", i),
             Value::Boolean(b) => write!(f, "{}", b),
+            Value::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            },
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            },
+            Value::Record { type_name, fields } => {
+                write!(f, "{} {{ ", type_name)?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, " }}")
+            },
+            Value::Variant { variant, fields, .. } => {
+                write!(f, "{}", variant)?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+                write!(f, " {{ ")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, " }}")
+            },
             Value::Null => write!(f, "null"),
+            Value::Builtin(name) => write!(f, "<built-in {}>", name),
             Value::Function { name, .. } => write!(f, "<function {}>", name),
         }
     }
 }
 
-#[derive(Clone)]
+impl Value {
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::String(_) => "String",
+            Value::Integer(_) => "Integer",
+            Value::Boolean(_) => "Boolean",
+            Value::List(_) => "List",
+            Value::Map(_) => "Map",
+            Value::Record { type_name, .. } => type_name,
+            Value::Variant { type_name, .. } => type_name,
+            Value::Null => "Null",
+            Value::Builtin(_) | Value::Function { .. } => "Function",
+        }
+    }
+}
+
+// The values that can be used as map keys. Keeping them apart from Value
+// lets maps be ordered, so they print and iterate the same way every time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Integer(i64),
+    String(String),
+}
+
+impl MapKey {
+    fn from_value(value: Value, expr: &Expression) -> Result<MapKey, Diagnostic<RuntimeError>> {
+        match value {
+            Value::Integer(i) => Ok(MapKey::Integer(i)),
+            Value::String(s) => Ok(MapKey::String(s)),
+            other => Err(Diagnostic::new(
+                RuntimeError::InvalidMapKey(other.type_name().to_string()),
+                expr.span,
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for MapKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapKey::Integer(i) => write!(f, "{}", i),
+            MapKey::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Integer(i) => Value::Integer(i),
+            MapKey::String(s) => Value::String(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    NotAFunction(String),
+    ArityMismatch {
+        expected: usize,
+        found: usize,
+    },
+    TypeMismatch {
+        expected: String,
+        found: String,
+    },
+    UnknownOperator(String),
+    DivisionByZero,
+    IntegerOverflow(String),
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    InvalidSlice {
+        start: i64,
+        end: i64,
+        length: usize,
+    },
+    InvalidMapKey(String),
+    KeyNotFound(String),
+    UnknownField {
+        type_name: String,
+        field: String,
+    },
+    NoMatchingArm(String),
+    RecursionLimit,
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::UndefinedVariable(name) => write!(f, "Undefined variable '{}'", name),
+            RuntimeError::UndefinedFunction(name) => write!(f, "Undefined function '{}'", name),
+            RuntimeError::NotAFunction(name) => write!(f, "'{}' is not a function", name),
+            RuntimeError::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} arguments but got {}", expected, found)
+            },
+            RuntimeError::TypeMismatch { expected, found } => {
+                write!(f, "Type mismatch: expected {}, got {}", expected, found)
+            },
+            RuntimeError::UnknownOperator(operator) => write!(f, "Unknown operator: {}", operator),
+            RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::IntegerOverflow(operator) => {
+                write!(f, "Integer overflow in '{}'", operator)
+            },
+            RuntimeError::IndexOutOfBounds { index, length } => {
+                write!(f, "Index {} is out of bounds for a list of length {}", index, length)
+            },
+            RuntimeError::InvalidSlice { start, end, length } => write!(
+                f,
+                "Slice from {} to {} is out of bounds for a list of length {}",
+                start, end, length
+            ),
+            RuntimeError::InvalidMapKey(found) => {
+                write!(f, "Map keys must be String or Integer, got {}", found)
+            },
+            RuntimeError::KeyNotFound(key) => write!(f, "Key '{}' not found in map", key),
+            RuntimeError::UnknownField { type_name, field } => {
+                write!(f, "No field '{}' on type {}", field, type_name)
+            },
+            RuntimeError::NoMatchingArm(variant) => {
+                write!(f, "No match arm for variant '{}'", variant)
+            },
+            RuntimeError::RecursionLimit => {
+                write!(f, "Calls nested more than {} deep", MAX_CALL_DEPTH)
+            },
+        }
+    }
+}
+
+impl ErrorCode for RuntimeError {
+    fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::UndefinedVariable(_) => "E0401",
+            RuntimeError::UndefinedFunction(_) => "E0402",
+            RuntimeError::NotAFunction(_) => "E0403",
+            RuntimeError::ArityMismatch { .. } => "E0404",
+            RuntimeError::TypeMismatch { .. } => "E0406",
+            RuntimeError::UnknownOperator(_) => "E0407",
+            RuntimeError::DivisionByZero => "E0408",
+            RuntimeError::IntegerOverflow(_) => "E0409",
+            RuntimeError::IndexOutOfBounds { .. } => "E0410",
+            RuntimeError::InvalidSlice { .. } => "E0411",
+            RuntimeError::InvalidMapKey(_) => "E0412",
+            RuntimeError::KeyNotFound(_) => "E0413",
+            RuntimeError::UnknownField { .. } => "E0414",
+            RuntimeError::NoMatchingArm(_) => "E0415",
+            RuntimeError::RecursionLimit => "E0416",
+        }
+    }
+}
+
+// How a statement finished. Anything other than Normal unwinds through the
+// enclosing blocks until something handles it.
+enum ControlFlow {
+    Normal(Value),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+// Scopes are shared rather than owned by their children, so a function can
+// keep the scope it was declared in and still see changes made to it later
 pub struct Environment {
     values: HashMap<String, Value>,
-    parent: Option<Box<Environment>>,
+    parent: Option<Rc<RefCell<Environment>>>,
+}
+
+// A function's closure usually contains the function itself, so only the
+// names are printed to keep Debug from going round in circles
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Environment")
+            .field("names", &self.values.keys())
+            .field("parent", &self.parent)
+            .finish()
+    }
 }
 
 impl Environment {
@@ -39,20 +297,19 @@ impl Environment {
             parent: None,
         }; 
         
-        // Add print function
-        env.define("print".to_string(), Value::Function {
-            name: "print".to_string(),
-            parameters: vec![],
-            body: vec![],
-        });
+        // A user function with the same name shadows a built-in
+        env.define("print".to_string(), Value::Builtin("print".to_string()));
+        for (name, _) in BUILTINS {
+            env.define(name.to_string(), Value::Builtin(name.to_string()));
+        }
 
         env
     }
     
-    pub fn extend(parent: Environment) -> Self {
+    pub fn extend(parent: Rc<RefCell<Environment>>) -> Self {
         Environment {
             values: HashMap::new(),
-            parent: Some(Box::new(parent)),
+            parent: Some(parent),
         }
     }
     
@@ -65,7 +322,7 @@ impl Environment {
             Some(value) => Some(value.clone()),
             None => {
                 if let Some(parent) = &self.parent {
-                    parent.get(name)
+                    parent.borrow().get(name)
                 } else {
                     None
                 }
@@ -73,66 +330,266 @@ impl Environment {
         }
     }
     
-    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
         if self.values.contains_key(name) {
             self.values.insert(name.to_string(), value);
             Ok(())
-        } else if let Some(parent) = &mut self.parent {
-            parent.assign(name, value)
+        } else if let Some(parent) = &self.parent {
+            parent.borrow_mut().assign(name, value)
         } else {
-            Err(format!("Undefined variable '{}'", name))
+            Err(RuntimeError::UndefinedVariable(name.to_string()))
         }
     }
 }
 
 pub struct Interpreter {
-    environment: Environment,
+    environment: Rc<RefCell<Environment>>,
+    // Field names of each declared record type, in declaration order
+    records: HashMap<String, Vec<String>>,
+    // The enum each variant belongs to, and the field names of its payload
+    variants: HashMap<String, (String, Vec<String>)>,
+    // How many user function calls are currently running
+    call_depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Interpreter {
-            environment: Environment::new(),
+            environment: Rc::new(RefCell::new(Environment::new())),
+            records: HashMap::new(),
+            variants: HashMap::new(),
+            call_depth: 0,
         }
     }
     
-    pub fn interpret(&mut self, program: Program) -> Result<(), String> {
-        for statement in program.statements {
-            self.execute_statement(&statement)?;
+    // Executes the program and returns the value of its last statement
+    pub fn interpret(&mut self, program: Program) -> Result<Value, Diagnostic<RuntimeError>> {
+        match self.execute_block(&program.statements)? {
+            ControlFlow::Normal(value) | ControlFlow::Return(value) => Ok(value),
+            // The type checker rejects these outside of loops
+            ControlFlow::Break | ControlFlow::Continue => Ok(Value::Null),
+        }
+    }
+    
+    // Runs statements in order, stopping early if one of them unwinds.
+    // Otherwise the result is the value of the last statement.
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<ControlFlow, Diagnostic<RuntimeError>> {
+        let mut result = Value::Null;
+        for statement in statements {
+            match self.execute_statement(statement)? {
+                ControlFlow::Normal(value) => result = value,
+                flow => return Ok(flow),
+            }
         }
-        Ok(())
+        Ok(ControlFlow::Normal(result))
     }
     
-    fn execute_statement(&mut self, statement: &Statement) -> Result<Value, String> {
-        match statement {
-            Statement::Expression(expr) => self.evaluate_expression(expr),
+    fn execute_statement(&mut self, statement: &Statement) -> Result<ControlFlow, Diagnostic<RuntimeError>> {
+        let value = match &statement.kind {
+            StatementKind::Expression(expr) => self.evaluate_expression(expr)?,
             
-            Statement::FunctionDeclaration { name, parameters, body } => {
+            StatementKind::FunctionDeclaration { name, parameters, body, .. } => {
                 let function = Value::Function {
                     name: name.clone(),
                     parameters: parameters.clone(),
                     body: body.clone(),
+                    closure: Rc::clone(&self.environment),
                 };
-                self.environment.define(name.clone(), function);
-                Ok(Value::Null)
+                self.environment.borrow_mut().define(name.clone(), function);
+                Value::Null
             },
             
-            Statement::IfStatement { condition, body } => {
+            StatementKind::IfStatement { condition, body, else_body } => {
                 let condition_value = self.evaluate_expression(condition)?;
                 
+                // Each branch gets its own scope, like the type checker gives it
                 if self.is_truthy(&condition_value) {
-                    let mut result = Value::Null;
-                    for stmt in body {
-                        result = self.execute_statement(stmt)?;
+                    return self.with_scope(|interpreter| interpreter.execute_block(body));
+                }
+                if let Some(else_body) = else_body {
+                    return self.with_scope(|interpreter| interpreter.execute_block(else_body));
+                }
+                Value::Null
+            },
+            
+            StatementKind::WhileStatement { condition, body } => {
+                loop {
+                    let condition_value = self.evaluate_expression(condition)?;
+                    if !self.is_truthy(&condition_value) {
+                        break;
+                    }
+                    
+                    match self.with_scope(|interpreter| interpreter.execute_block(body))? {
+                        ControlFlow::Break => break,
+                        ControlFlow::Normal(_) | ControlFlow::Continue => {},
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
                     }
-                    Ok(result)
-                } else {
-                    Ok(Value::Null)
                 }
+                Value::Null
             },
             
-            Statement::Comment(_) => Ok(Value::Null),
-        }
+            StatementKind::ForStatement { variable, iterable, end, body } => {
+                let first = self.evaluate_expression(iterable)?;
+
+                // A range counts up without building a list of every number
+                let items: Box<dyn Iterator<Item = Value>> = match end {
+                    Some(end) => {
+                        let end_value = self.evaluate_expression(end)?;
+                        let from = Self::expect_integer(first, iterable)?;
+                        let to = Self::expect_integer(end_value, end)?;
+                        Box::new((from..to).map(Value::Integer))
+                    },
+                    None => match first {
+                        Value::List(elements) => Box::new(elements.into_iter()),
+                        Value::Map(entries) => Box::new(entries.into_keys().map(Value::from)),
+                        other => {
+                            return Err(Diagnostic::new(
+                                RuntimeError::TypeMismatch {
+                                    expected: "List or Map".to_string(),
+                                    found: other.type_name().to_string(),
+                                },
+                                iterable.span,
+                            ));
+                        },
+                    },
+                };
+
+                for item in items {
+                    let flow = self.with_scope(|interpreter| {
+                        interpreter.environment.borrow_mut().define(variable.clone(), item);
+                        interpreter.execute_block(body)
+                    })?;
+
+                    match flow {
+                        ControlFlow::Break => break,
+                        ControlFlow::Normal(_) | ControlFlow::Continue => {},
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
+                    }
+                }
+                Value::Null
+            },
+            
+            StatementKind::Break => return Ok(ControlFlow::Break),
+            
+            StatementKind::Continue => return Ok(ControlFlow::Continue),
+            
+            StatementKind::VariableDeclaration { name, value, .. } => {
+                let value = self.evaluate_expression(value)?;
+                self.environment.borrow_mut().define(name.clone(), value);
+                Value::Null
+            },
+            
+            StatementKind::Assignment { name, value } => {
+                let value = self.evaluate_expression(value)?;
+                self.environment
+                    .borrow_mut()
+                    .assign(name, value)
+                    .map_err(|e| Diagnostic::new(e, statement.span))?;
+                Value::Null
+            },
+            
+            StatementKind::FieldAssignment { name, fields, value } => {
+                let new_value = self.evaluate_expression(value)?;
+                let mut record = self.environment.borrow().get(name).ok_or_else(|| {
+                    Diagnostic::new(RuntimeError::UndefinedVariable(name.clone()), statement.span)
+                })?;
+
+                // Records are values, so the updated copy replaces the variable
+                Self::set_field(&mut record, fields, new_value)
+                    .map_err(|e| Diagnostic::new(e, statement.span))?;
+                self.environment
+                    .borrow_mut()
+                    .assign(name, record)
+                    .map_err(|e| Diagnostic::new(e, statement.span))?;
+                Value::Null
+            },
+            
+            StatementKind::TypeDeclaration { name, fields } => {
+                let field_names = fields.iter().map(|field| field.name.clone()).collect();
+                self.records.insert(name.clone(), field_names);
+                Value::Null
+            },
+            
+            StatementKind::EnumDeclaration { name, variants } => {
+                for variant in variants {
+                    let field_names = variant.fields.iter().map(|field| field.name.clone()).collect();
+                    self.variants.insert(variant.name.clone(), (name.clone(), field_names));
+                }
+                Value::Null
+            },
+            
+            StatementKind::Match { value, arms } => {
+                let (variant, fields) = match self.evaluate_expression(value)? {
+                    Value::Variant { variant, fields, .. } => (variant, fields),
+                    other => {
+                        return Err(Diagnostic::new(
+                            RuntimeError::TypeMismatch {
+                                expected: "enum".to_string(),
+                                found: other.type_name().to_string(),
+                            },
+                            value.span,
+                        ));
+                    },
+                };
+                
+                // The first arm naming the variant wins, or else the 'else' arm
+                let Some(arm) = arms
+                    .iter()
+                    .find(|arm| arm.variant.as_ref().is_none_or(|name| *name == variant))
+                else {
+                    return Err(Diagnostic::new(RuntimeError::NoMatchingArm(variant), statement.span));
+                };
+                
+                let mut bindings = Vec::new();
+                for (field, binding) in &arm.bindings {
+                    let Some((_, field_value)) = fields.iter().find(|(name, _)| name == field) else {
+                        return Err(Diagnostic::new(
+                            RuntimeError::UnknownField {
+                                type_name: variant,
+                                field: field.clone(),
+                            },
+                            arm.span,
+                        ));
+                    };
+                    bindings.push((binding.clone(), field_value.clone()));
+                }
+                
+                // Payload fields are bound in the arm's own scope
+                return self.with_scope(|interpreter| {
+                    for (binding, field_value) in bindings {
+                        interpreter.environment.borrow_mut().define(binding, field_value);
+                    }
+                    interpreter.execute_block(&arm.body)
+                });
+            },
+            
+            StatementKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate_expression(value)?,
+                    None => Value::Null,
+                };
+                return Ok(ControlFlow::Return(value));
+            },
+            
+            StatementKind::Comment(_) => Value::Null,
+        };
+        
+        Ok(ControlFlow::Normal(value))
+    }
+    
+    // Runs `f` in a new block scope nested inside the current one
+    fn with_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let scope = Environment::extend(Rc::clone(&self.environment));
+        self.in_environment(scope, f)
+    }
+    
+    // Runs `f` with `environment` as the current scope, then goes back to
+    // the one that was current before
+    fn in_environment<T>(&mut self, environment: Environment, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = f(self);
+        self.environment = previous;
+        result
     }
     
     fn is_truthy(&self, value: &Value) -> bool {
@@ -141,50 +598,73 @@ impl Interpreter {
             Value::Null => false,
             Value::Integer(i) => *i != 0,
             Value::String(s) => !s.is_empty(),
-            Value::Function { .. } => true,
+            Value::List(elements) => !elements.is_empty(),
+            Value::Map(entries) => !entries.is_empty(),
+            Value::Record { .. } | Value::Variant { .. } => true,
+            Value::Builtin(_) | Value::Function { .. } => true,
         }
     }
     
-    fn evaluate_expression(&mut self, expr: &Expression) -> Result<Value, String> {
-        match expr {
-            Expression::StringLiteral(s) => Ok(Value::String(s.clone())),
+    fn evaluate_expression(&mut self, expr: &Expression) -> Result<Value, Diagnostic<RuntimeError>> {
+        match &expr.kind {
+            ExpressionKind::StringLiteral(s) => Ok(Value::String(s.clone())),
             
-            Expression::IntegerLiteral(i) => Ok(Value::Integer(*i)),
+            ExpressionKind::IntegerLiteral(i) => Ok(Value::Integer(*i)),
+            // Programs with parse errors are never run, so this isn't reached
+            ExpressionKind::Error => Ok(Value::Null),
+            ExpressionKind::BooleanLiteral(b) => Ok(Value::Boolean(*b)),
             
-            Expression::Identifier(name) => {
-                match self.environment.get(name) {
+            ExpressionKind::Identifier(name) => {
+                match self.environment.borrow().get(name) {
                     Some(value) => Ok(value),
-                    None => Err(format!("Undefined variable '{}'", name)),
+                    // A variant without a payload is written by its name alone
+                    None if self.variants.get(name).is_some_and(|(_, fields)| fields.is_empty()) => {
+                        let (type_name, _) = &self.variants[name];
+                        Ok(Value::Variant {
+                            type_name: type_name.clone(),
+                            variant: name.clone(),
+                            fields: Vec::new(),
+                        })
+                    },
+                    None => Err(Diagnostic::new(
+                        RuntimeError::UndefinedVariable(name.clone()),
+                        expr.span,
+                    )),
                 }
             },
             
-            Expression::FunctionCall { name, arguments } => {
-                let function = self.environment.get(name)
-                    .ok_or_else(|| format!("Undefined function '{}'", name))?;
+            ExpressionKind::FunctionCall { name, arguments } => {
+                let function = self.environment.borrow().get(name)
+                    .ok_or_else(|| Diagnostic::new(
+                        RuntimeError::UndefinedFunction(name.clone()),
+                        expr.span,
+                    ))?;
                 
                 match function {
-                    Value::Function { name, parameters, body } => {
-                        // Special case for built-in print function
-                        if name == "print" {
-                            let mut arg_values = Vec::new();
-                            for arg in arguments {
-                                let value = self.evaluate_expression(arg)?;
-                                arg_values.push(value);
-                            }
-                            
+                    Value::Builtin(builtin) => {
+                        let mut arg_values = Vec::new();
+                        for arg in arguments {
+                            arg_values.push(self.evaluate_expression(arg)?);
+                        }
+                        
+                        if builtin == "print" {
                             for value in arg_values {
                                 println!("{}", value);
                             }
-                            
                             return Ok(Value::Null);
                         }
-
-                        // User-defined function
+                        
+                        Self::call_builtin(&builtin, arg_values, arguments, expr)
+                    },
+                    
+                    Value::Function { parameters, body, closure, .. } => {
                         if arguments.len() != parameters.len() {
-                            return Err(format!(
-                                "Expected {} arguments but got {}",
-                                parameters.len(),
-                                arguments.len()
+                            return Err(Diagnostic::new(
+                                RuntimeError::ArityMismatch {
+                                    expected: parameters.len(),
+                                    found: arguments.len(),
+                                },
+                                expr.span,
                             ));
                         }
                         
@@ -194,74 +674,399 @@ impl Interpreter {
                             arg_values.push(value);
                         }
                         
-                        let mut env = Environment::extend(self.environment.clone());
-                        
-                        for (param, value) in parameters.iter().zip(arg_values) {
-                            env.define(param.name.clone(), value);
-                        }
-                        
-                        let previous_env = std::mem::replace(&mut self.environment, env);
-                        
-                        let mut result = Value::Null;
-                        for stmt in &body {
-                            result = self.execute_statement(stmt)?;
+                        if self.call_depth >= MAX_CALL_DEPTH {
+                            return Err(Diagnostic::new(RuntimeError::RecursionLimit, expr.span)
+                                .with_note("check that the recursion has a case where it stops"));
                         }
                         
-                        self.environment = previous_env;
-                        Ok(result)
+                        // The body sees the names around its declaration, not the caller's
+                        self.call_depth += 1;
+                        let result = self.in_environment(Environment::extend(closure), |interpreter| {
+                            for (param, value) in parameters.iter().zip(arg_values) {
+                                interpreter.environment.borrow_mut().define(param.name.clone(), value);
+                            }
+                            
+                            // Falling off the end of the body returns nothing
+                            match interpreter.execute_block(&body)? {
+                                ControlFlow::Return(value) => Ok(value),
+                                _ => Ok(Value::Null),
+                            }
+                        });
+                        self.call_depth -= 1;
+                        result
                     },
-                    _ => Err(format!("'{}' is not a function", name)),
+                    _ => Err(Diagnostic::new(RuntimeError::NotAFunction(name.clone()), expr.span)),
                 }
             },
             
-            Expression::TypedValue { type_name, value } => {
-                // Special case for String[Hello] and similar constructs
-                if let Expression::Identifier(ident) = &**value {
-                    match type_name.as_str() {
-                        "String" => return Ok(Value::String(ident.clone())),
-                        "Integer" => {
-                            if let Ok(i) = ident.parse::<i64>() {
-                                return Ok(Value::Integer(i));
-                            } else {
-                                return Err(format!("Cannot convert '{}' to Integer", ident));
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                
-                let inner_value = self.evaluate_expression(value)?;
-                
-                // Type checking
-                match (type_name.as_str(), &inner_value) {
-                    ("String", Value::String(_)) => Ok(inner_value),
-                    ("Integer", Value::Integer(_)) => Ok(inner_value),
-                    _ => Err(format!(
-                        "Type mismatch: expected {}, got {:?}", 
-                        type_name, 
-                        inner_value
-                    )),
+            ExpressionKind::BinaryOperation { left, operator, right } if operator == "and" || operator == "or" => {
+                let left_value = self.evaluate_expression(left)?;
+
+                // The right side is only evaluated when the left one doesn't
+                // already decide the result
+                if Self::expect_boolean(left_value, left)? == (operator == "or") {
+                    return Ok(Value::Boolean(operator == "or"));
                 }
+
+                let right_value = self.evaluate_expression(right)?;
+                Ok(Value::Boolean(Self::expect_boolean(right_value, right)?))
             },
-            
-            Expression::BinaryOperation { left, operator, right } => {
+
+            ExpressionKind::BinaryOperation { left, operator, right } => {
                 let left_value = self.evaluate_expression(left)?;
                 let right_value = self.evaluate_expression(right)?;
                 
                 match operator.as_str() {
                     "is" => Ok(Value::Boolean(self.values_equal(&left_value, &right_value))),
                     "is not" => Ok(Value::Boolean(!self.values_equal(&left_value, &right_value))),
-                    _ => Err(format!("Unknown operator: {}", operator)),
+                    "+" | "-" | "*" | "/" | "%" => {
+                        let a = Self::expect_integer(left_value, left)?;
+                        let b = Self::expect_integer(right_value, right)?;
+
+                        if b == 0 && (operator == "/" || operator == "%") {
+                            return Err(Diagnostic::new(RuntimeError::DivisionByZero, right.span));
+                        }
+
+                        let result = match operator.as_str() {
+                            "+" => a.checked_add(b),
+                            "-" => a.checked_sub(b),
+                            "*" => a.checked_mul(b),
+                            "/" => a.checked_div(b),
+                            _ => a.checked_rem(b),
+                        };
+
+                        result.map(Value::Integer).ok_or_else(|| {
+                            Diagnostic::new(RuntimeError::IntegerOverflow(operator.clone()), expr.span)
+                        })
+                    },
+                    "<" | "<=" | ">" | ">=" => {
+                        let ordering = match (&left_value, &right_value) {
+                            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+                            (Value::String(a), Value::String(b)) => a.cmp(b),
+                            _ => {
+                                return Err(Diagnostic::new(
+                                    RuntimeError::TypeMismatch {
+                                        expected: left_value.type_name().to_string(),
+                                        found: right_value.type_name().to_string(),
+                                    },
+                                    right.span,
+                                ));
+                            },
+                        };
+
+                        Ok(Value::Boolean(match operator.as_str() {
+                            "<" => ordering.is_lt(),
+                            "<=" => ordering.is_le(),
+                            ">" => ordering.is_gt(),
+                            _ => ordering.is_ge(),
+                        }))
+                    },
+                    _ => Err(Diagnostic::new(
+                        RuntimeError::UnknownOperator(operator.clone()),
+                        expr.span,
+                    )),
+                }
+            },
+
+            ExpressionKind::ListLiteral(elements) => {
+                let mut values = Vec::new();
+                for element in elements {
+                    values.push(self.evaluate_expression(element)?);
+                }
+                Ok(Value::List(values))
+            },
+
+            ExpressionKind::RecordLiteral { type_name, fields } => {
+                let mut values = Vec::new();
+                for field in fields {
+                    values.push((field.name.clone(), self.evaluate_expression(&field.value)?));
+                }
+
+                // Put the fields in declaration order, so records of the same
+                // type always print and compare alike
+                if let Some(order) = self.records.get(type_name) {
+                    values.sort_by_key(|(name, _)| order.iter().position(|field| field == name));
+                }
+
+                // Variants of an enum are built the same way as records
+                if let Some((enum_name, order)) = self.variants.get(type_name) {
+                    values.sort_by_key(|(name, _)| order.iter().position(|field| field == name));
+                    return Ok(Value::Variant {
+                        type_name: enum_name.clone(),
+                        variant: type_name.clone(),
+                        fields: values,
+                    });
+                }
+
+                Ok(Value::Record {
+                    type_name: type_name.clone(),
+                    fields: values,
+                })
+            },
+
+            ExpressionKind::FieldAccess { target, field } => {
+                let value = self.evaluate_expression(target)?;
+                let unknown_field = RuntimeError::UnknownField {
+                    type_name: value.type_name().to_string(),
+                    field: field.clone(),
+                };
+
+                match value {
+                    Value::Record { fields, .. } => fields
+                        .into_iter()
+                        .find(|(name, _)| name == field)
+                        .map(|(_, value)| value)
+                        .ok_or_else(|| Diagnostic::new(unknown_field, expr.span)),
+                    _ => Err(Diagnostic::new(unknown_field, expr.span)),
+                }
+            },
+
+            ExpressionKind::MapLiteral(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key_value = self.evaluate_expression(key)?;
+                    let map_key = MapKey::from_value(key_value, key)?;
+                    map.insert(map_key, self.evaluate_expression(value)?);
+                }
+                Ok(Value::Map(map))
+            },
+
+            ExpressionKind::Index { target, index } => {
+                let target_value = self.evaluate_expression(target)?;
+                let index_value = self.evaluate_expression(index)?;
+
+                // Unlike get, indexing a map expects the key to be there
+                if let Value::Map(mut entries) = target_value {
+                    let key = MapKey::from_value(index_value, index)?;
+                    return entries.remove(&key).ok_or_else(|| {
+                        Diagnostic::new(RuntimeError::KeyNotFound(key.to_string()), index.span)
+                    });
                 }
+
+                let i = Self::expect_integer(index_value, index)?;
+                let elements = Self::expect_list(target_value, target)?;
+
+                usize::try_from(i)
+                    .ok()
+                    .and_then(|i| elements.get(i).cloned())
+                    .ok_or_else(|| {
+                        Diagnostic::new(
+                            RuntimeError::IndexOutOfBounds { index: i, length: elements.len() },
+                            index.span,
+                        )
+                    })
+            },
+
+            ExpressionKind::UnaryOperation { operator, operand } if operator == "not" => {
+                let value = self.evaluate_expression(operand)?;
+                Ok(Value::Boolean(!Self::expect_boolean(value, operand)?))
+            },
+
+            ExpressionKind::UnaryOperation { operator, operand } => {
+                let value = self.evaluate_expression(operand)?;
+                let value = Self::expect_integer(value, operand)?;
+
+                value.checked_neg().map(Value::Integer).ok_or_else(|| {
+                    Diagnostic::new(RuntimeError::IntegerOverflow(operator.clone()), expr.span)
+                })
             },
         }
     }
     
+    fn expect_integer(value: Value, expr: &Expression) -> Result<i64, Diagnostic<RuntimeError>> {
+        match value {
+            Value::Integer(i) => Ok(i),
+            other => Err(Diagnostic::new(
+                RuntimeError::TypeMismatch {
+                    expected: "Integer".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                expr.span,
+            )),
+        }
+    }
+    
+    fn expect_list(value: Value, expr: &Expression) -> Result<Vec<Value>, Diagnostic<RuntimeError>> {
+        match value {
+            Value::List(elements) => Ok(elements),
+            other => Err(Diagnostic::new(
+                RuntimeError::TypeMismatch {
+                    expected: "List".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                expr.span,
+            )),
+        }
+    }
+    
+    // Runs one of the list or map built-ins on already evaluated arguments.
+    // Collections are values, so the ones that change them hand back a new one.
+    fn call_builtin(
+        name: &str,
+        mut values: Vec<Value>,
+        arguments: &[Expression],
+        call: &Expression,
+    ) -> Result<Value, Diagnostic<RuntimeError>> {
+        let expected = BUILTINS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map_or(0, |(_, arity)| *arity);
+        if values.len() != expected {
+            return Err(Diagnostic::new(
+                RuntimeError::ArityMismatch {
+                    expected,
+                    found: values.len(),
+                },
+                call.span,
+            ));
+        }
+
+        let mut rest = values.split_off(1).into_iter();
+        let target = values.remove(0);
+
+        match name {
+            "length" => {
+                let length = match target {
+                    Value::String(s) => s.chars().count(),
+                    Value::Map(entries) => entries.len(),
+                    other => Self::expect_list(other, &arguments[0])?.len(),
+                };
+                Ok(Value::Integer(length as i64))
+            },
+            "push" => {
+                let mut elements = Self::expect_list(target, &arguments[0])?;
+                elements.extend(rest);
+                Ok(Value::List(elements))
+            },
+            "slice" => {
+                let elements = Self::expect_list(target, &arguments[0])?;
+                let start = Self::expect_integer(rest.next().unwrap_or(Value::Null), &arguments[1])?;
+                let end = Self::expect_integer(rest.next().unwrap_or(Value::Null), &arguments[2])?;
+
+                // The end is exclusive, so slice(xs, 0, length(xs)) copies the list
+                match (usize::try_from(start), usize::try_from(end)) {
+                    (Ok(from), Ok(to)) if from <= to && to <= elements.len() => {
+                        Ok(Value::List(elements[from..to].to_vec()))
+                    },
+                    _ => Err(Diagnostic::new(
+                        RuntimeError::InvalidSlice {
+                            start,
+                            end,
+                            length: elements.len(),
+                        },
+                        call.span,
+                    )),
+                }
+            },
+            _ => {
+                let mut entries = Self::expect_map(target, &arguments[0])?;
+
+                match name {
+                    "keys" => Ok(Value::List(entries.into_keys().map(Value::from).collect())),
+                    "values" => Ok(Value::List(entries.into_values().collect())),
+                    _ => {
+                        let key = MapKey::from_value(rest.next().unwrap_or(Value::Null), &arguments[1])?;
+
+                        match name {
+                            // A missing key is not an error here, it just gives back nothing
+                            "get" => Ok(entries.remove(&key).unwrap_or(Value::Null)),
+                            "has" => Ok(Value::Boolean(entries.contains_key(&key))),
+                            "insert" => {
+                                entries.insert(key, rest.next().unwrap_or(Value::Null));
+                                Ok(Value::Map(entries))
+                            },
+                            _ => {
+                                entries.remove(&key);
+                                Ok(Value::Map(entries))
+                            },
+                        }
+                    },
+                }
+            },
+        }
+    }
+
+    // Replaces the field at the end of `path` inside `value`
+    fn set_field(value: &mut Value, path: &[String], new_value: Value) -> Result<(), RuntimeError> {
+        let Some((field, rest)) = path.split_first() else {
+            *value = new_value;
+            return Ok(());
+        };
+
+        let unknown_field = RuntimeError::UnknownField {
+            type_name: value.type_name().to_string(),
+            field: field.clone(),
+        };
+        let Value::Record { fields, .. } = value else {
+            return Err(unknown_field);
+        };
+
+        match fields.iter_mut().find(|(name, _)| name == field) {
+            Some((_, field_value)) => Self::set_field(field_value, rest, new_value),
+            None => Err(unknown_field),
+        }
+    }
+
+    fn expect_map(value: Value, expr: &Expression) -> Result<BTreeMap<MapKey, Value>, Diagnostic<RuntimeError>> {
+        match value {
+            Value::Map(entries) => Ok(entries),
+            other => Err(Diagnostic::new(
+                RuntimeError::TypeMismatch {
+                    expected: "Map".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                expr.span,
+            )),
+        }
+    }
+    
+    fn expect_boolean(value: Value, expr: &Expression) -> Result<bool, Diagnostic<RuntimeError>> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            other => Err(Diagnostic::new(
+                RuntimeError::TypeMismatch {
+                    expected: "Boolean".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                expr.span,
+            )),
+        }
+    }
+    
     fn values_equal(&self, left: &Value, right: &Value) -> bool {
         match (left, right) {
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Integer(a), Value::Integer(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.values_equal(a, b))
+            },
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|((a_key, a_value), (b_key, b_value))| {
+                        a_key == b_key && self.values_equal(a_value, b_value)
+                    })
+            },
+            (
+                Value::Record { type_name: a_type, fields: a_fields },
+                Value::Record { type_name: b_type, fields: b_fields },
+            ) => {
+                a_type == b_type
+                    && a_fields.len() == b_fields.len()
+                    && a_fields.iter().zip(b_fields).all(|((a_name, a_value), (b_name, b_value))| {
+                        a_name == b_name && self.values_equal(a_value, b_value)
+                    })
+            },
+            (
+                Value::Variant { variant: a_variant, fields: a_fields, .. },
+                Value::Variant { variant: b_variant, fields: b_fields, .. },
+            ) => {
+                a_variant == b_variant
+                    && a_fields.len() == b_fields.len()
+                    && a_fields.iter().zip(b_fields).all(|((_, a_value), (_, b_value))| {
+                        self.values_equal(a_value, b_value)
+                    })
+            },
             (Value::Null, Value::Null) => true,
             _ => false,
         }
@@ -1,19 +1,71 @@
-use std::str::Chars;
-use std::iter::Peekable;
+use crate::diagnostic::ErrorCode;
+use crate::span::Span;
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
+pub enum LexError {
+    UnterminatedLiteral(String),
+    InvalidEscape(String),
+    ParenthesizedLiteral(String),
+    InvalidLiteral {
+        type_name: String,
+        value: String,
+    },
+    IntegerOutOfRange(String),
+    UnexpectedCharacter(char),
+    UnterminatedComment,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::UnterminatedLiteral(type_name) => {
+                write!(f, "Unterminated {} literal", type_name)
+            },
+            LexError::InvalidEscape(escape) => write!(f, "Invalid escape sequence '{}'", escape),
+            LexError::ParenthesizedLiteral(type_name) => {
+                write!(f, "{}(...) literals are not supported", type_name)
+            },
+            LexError::InvalidLiteral { type_name, value } => {
+                write!(f, "Invalid {} literal '{}'", type_name, value)
+            },
+            LexError::IntegerOutOfRange(value) => {
+                write!(f, "Integer literal '{}' is out of range", value)
+            },
+            LexError::UnexpectedCharacter(c) => write!(f, "Unexpected character '{}'", c),
+            LexError::UnterminatedComment => write!(f, "Unterminated block comment"),
+        }
+    }
+}
+
+impl ErrorCode for LexError {
+    fn code(&self) -> &'static str {
+        match self {
+            LexError::UnterminatedLiteral(_) => "E0101",
+            LexError::InvalidEscape(_) => "E0102",
+            LexError::ParenthesizedLiteral(_) => "E0103",
+            LexError::InvalidLiteral { .. } => "E0104",
+            LexError::IntegerOutOfRange(_) => "E0105",
+            LexError::UnexpectedCharacter(_) => "E0106",
+            LexError::UnterminatedComment => "E0107",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenKind {
     // Identifiers and literals
     Identifier(String),
     StringLiteral(String),
     IntegerLiteral(i64),
+    BooleanLiteral(bool),
 
     // Types
     TypeString,
     TypeInteger,
+    TypeBoolean,
+    TypeList,
+    TypeMap,
     TypeUnknown,
-    TypeTrue,
-    TypeFalse,
 
     // Symbols
     LeftBracket,      // [
@@ -26,40 +78,185 @@ pub enum Token {
     NotEquals,        // is not
     Colon,            // :
     Comma,            // ,
+    Dot,              // .
+    Assign,           // =
+    Plus,             // +
+    Minus,            // -
+    Star,             // *
+    Slash,            // /
+    Percent,          // %
+    Less,             // <
+    LessEqual,        // <=
+    Greater,          // >
+    GreaterEqual,     // >=
 
     // Keywords
     If,
+    Else,
+    While,
+    For,
+    In,
+    To,
+    Break,
+    Continue,
     Func,
+    Type,
+    Enum,
+    Match,
+    Let,
+    Return,
+    And,
+    Or,
+    Not,
 
     // Comments
     Comment(String),
+    DocComment(String),
+
+    // Produced for malformed input, reported by the parser
+    Error(LexError),
 
     // End of file
-    EOF,
+    Eof,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Identifier(name) => write!(f, "identifier '{}'", name),
+            TokenKind::StringLiteral(s) => write!(f, "string literal '{}'", s),
+            TokenKind::IntegerLiteral(i) => write!(f, "integer literal {}", i),
+            TokenKind::BooleanLiteral(true) => write!(f, "'True'"),
+            TokenKind::BooleanLiteral(false) => write!(f, "'False'"),
+            TokenKind::TypeString => write!(f, "'String'"),
+            TokenKind::TypeInteger => write!(f, "'Integer'"),
+            TokenKind::TypeBoolean => write!(f, "'Boolean'"),
+            TokenKind::TypeList => write!(f, "'List'"),
+            TokenKind::TypeMap => write!(f, "'Map'"),
+            TokenKind::TypeUnknown => write!(f, "'Unknown'"),
+            TokenKind::LeftBracket => write!(f, "'['"),
+            TokenKind::RightBracket => write!(f, "']'"),
+            TokenKind::LeftBrace => write!(f, "'{{'"),
+            TokenKind::RightBrace => write!(f, "'}}'"),
+            TokenKind::LeftParen => write!(f, "'('"),
+            TokenKind::RightParen => write!(f, "')'"),
+            TokenKind::Equals => write!(f, "'is'"),
+            TokenKind::NotEquals => write!(f, "'is not'"),
+            TokenKind::Colon => write!(f, "':'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Dot => write!(f, "'.'"),
+            TokenKind::Assign => write!(f, "'='"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Percent => write!(f, "'%'"),
+            TokenKind::Less => write!(f, "'<'"),
+            TokenKind::LessEqual => write!(f, "'<='"),
+            TokenKind::Greater => write!(f, "'>'"),
+            TokenKind::GreaterEqual => write!(f, "'>='"),
+            TokenKind::If => write!(f, "'if'"),
+            TokenKind::Else => write!(f, "'else'"),
+            TokenKind::While => write!(f, "'while'"),
+            TokenKind::For => write!(f, "'for'"),
+            TokenKind::In => write!(f, "'in'"),
+            TokenKind::To => write!(f, "'to'"),
+            TokenKind::Break => write!(f, "'break'"),
+            TokenKind::Continue => write!(f, "'continue'"),
+            TokenKind::Func => write!(f, "'func'"),
+            TokenKind::Type => write!(f, "'type'"),
+            TokenKind::Enum => write!(f, "'enum'"),
+            TokenKind::Match => write!(f, "'match'"),
+            TokenKind::Let => write!(f, "'let'"),
+            TokenKind::Return => write!(f, "'return'"),
+            TokenKind::And => write!(f, "'and'"),
+            TokenKind::Or => write!(f, "'or'"),
+            TokenKind::Not => write!(f, "'not'"),
+            TokenKind::Comment(_) => write!(f, "comment"),
+            TokenKind::DocComment(_) => write!(f, "doc comment"),
+            TokenKind::Error(e) => write!(f, "{}", e),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    // Whether a line break separates this token from the one before it
+    pub starts_line: bool,
 }
 
+impl Token {
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        Token { kind, span, starts_line: false }
+    }
+}
+
+#[derive(Clone)]
 pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>,
+    input: &'a str,
+    position: usize,
+    line: usize,
+    column: usize,
     current_char: Option<char>,
+    // Whether the last token could end an operand. A '-' directly before a
+    // digit only starts a negative literal when it can't be a subtraction.
+    after_operand: bool,
+    // Skip characters that don't start any token instead of reporting them
+    lenient: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
-        let mut chars = input.chars().peekable();
-        let current_char = chars.next();
-
         Lexer {
-            input: chars,
-            current_char,
+            input,
+            position: 0,
+            line: 1,
+            column: 1,
+            current_char: input.chars().next(),
+            after_operand: false,
+            lenient: false,
         }
     }
 
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    // Starts lexing partway into the input, so spans stay relative to the
+    // whole input rather than to the part being lexed
+    pub fn starting_at(mut self, position: usize) -> Self {
+        let before = &self.input[..position];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+        self.position = position;
+        self.line = before.matches('\n').count() + 1;
+        self.column = before[line_start..].chars().count() + 1;
+        self.current_char = self.input[position..].chars().next();
+        self
+    }
+
     fn advance(&mut self) {
-        self.current_char = self.input.next();
+        if let Some(c) = self.current_char {
+            self.position += c.len_utf8();
+
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        self.current_char = self.input[self.position..].chars().next();
     }
 
-    fn peek(&mut self) -> Option<&char> {
-        self.input.peek()
+    fn peek(&self) -> Option<char> {
+        let c = self.current_char?;
+        self.input[self.position + c.len_utf8()..].chars().next()
     }
 
     fn skip_whitespace(&mut self) {
@@ -88,7 +285,34 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
         
-        comment.trim().to_string()
+        comment
+    }
+
+    // Skips a `/* ... */` comment. Block comments nest, so each '/*' inside
+    // needs its own '*/'.
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let mut depth = 0;
+
+        while let Some(c) = self.current_char {
+            match (c, self.peek()) {
+                ('/', Some('*')) => {
+                    self.advance();
+                    depth += 1;
+                },
+                ('*', Some('/')) => {
+                    self.advance();
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance();
+                        return Ok(());
+                    }
+                },
+                _ => {},
+            }
+            self.advance();
+        }
+
+        Err(LexError::UnterminatedComment)
     }
 
     fn read_identifier(&mut self) -> String {
@@ -106,22 +330,32 @@ impl<'a> Lexer<'a> {
         identifier
     }
 
-    fn read_number(&mut self) -> i64 {
-        let mut number = String::new();
+    // Reads a bare integer literal, including a leading '-' if there is one.
+    // Letters and underscores are taken too, so that prefixes and separators
+    // are part of the literal and `12abc` is reported rather than split up.
+    fn read_number(&mut self) -> TokenKind {
+        let start = self.position;
 
+        if self.current_char == Some('-') {
+            self.advance();
+        }
         while let Some(c) = self.current_char {
-            if c.is_digit(10) {
-                number.push(c);
+            if c.is_alphanumeric() || c == '_' {
                 self.advance();
             } else {
                 break;
             }
         }
 
-        number.parse::<i64>().unwrap_or(0)
+        match parse_integer(&self.input[start..self.position]) {
+            Ok(number) => TokenKind::IntegerLiteral(number),
+            Err(e) => TokenKind::Error(e),
+        }
     }
 
-    fn read_string_literal(&mut self) -> String {
+    // Reads the contents of an old-style `Type(...)` literal. Returns None if
+    // the input ends before the closing parenthesis.
+    fn read_string_literal(&mut self) -> Option<String> {
         let mut string = String::new();
 
         // Skip the opening parenthesis
@@ -131,164 +365,356 @@ impl<'a> Lexer<'a> {
             if c == ')' {
                 // End of string literal
                 self.advance();
-                break;
+                return Some(string);
             } else {
                 string.push(c);
                 self.advance();
             }
         }
 
-        string
+        None
     }
 
-    fn read_type_value(&mut self) -> Token {
-        let identifier = self.read_identifier();
+    // Reads the raw text of a `Type[...]` literal up to the matching ']'.
+    // Brackets inside nest, and '\' escapes ']', '[', '\\', 'n', 't' and
+    // unicode characters written as `\u{...}`.
+    fn read_raw_string(&mut self, type_name: &str) -> Result<String, LexError> {
+        let mut string = String::new();
+        let mut depth = 0;
+        let mut error = None;
+
+        // Skip the opening bracket
+        self.advance();
+
+        while let Some(c) = self.current_char {
+            self.advance();
 
-        // Check if the next character is an opening parenthesis
-        if let Some('(') = self.current_char {
-            match identifier.as_str() {
-                "String" => {
-                    let string_value = self.read_string_literal();
-                    return Token::StringLiteral(string_value);
+            match c {
+                '\\' => match self.read_escape() {
+                    Ok(escaped) => string.push(escaped),
+                    // Keep going so the rest of the literal isn't lexed as code
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    },
                 },
-                "Integer" => {
-                    // For Integer, we need to parse the content as a number
-                    let string_value = self.read_string_literal();
-                    if let Ok(int_value) = string_value.parse::<i64>() {
-                        return Token::IntegerLiteral(int_value);
-                    } else {
-                        // If parsing fails, return 0 or handle error
-                        return Token::IntegerLiteral(0);
-                    }
+                '[' => {
+                    depth += 1;
+                    string.push(c);
+                },
+                ']' if depth == 0 => return error.map_or(Ok(string), Err),
+                ']' => {
+                    depth -= 1;
+                    string.push(c);
                 },
-                _ => {}
+                _ => string.push(c),
             }
         }
 
-        // If not followed by a parenthesis or not a known type
+        Err(LexError::UnterminatedLiteral(type_name.to_string()))
+    }
+
+    // Reads the part of an escape sequence after the '\'
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        let Some(c) = self.current_char else {
+            return Err(LexError::InvalidEscape("\\".to_string()));
+        };
+        self.advance();
+
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            '\\' | '[' | ']' => Ok(c),
+            'u' => {
+                let mut escape = String::from("\\u");
+                if self.current_char != Some('{') {
+                    return Err(LexError::InvalidEscape(escape));
+                }
+                escape.push('{');
+                self.advance();
+
+                // Stop at anything that isn't a hex digit, so a missing '}'
+                // doesn't swallow the literal's closing bracket
+                while let Some(c) = self.current_char.filter(|c| c.is_ascii_hexdigit()) {
+                    escape.push(c);
+                    self.advance();
+                }
+                if self.current_char == Some('}') {
+                    escape.push('}');
+                    self.advance();
+                }
+
+                escape
+                    .strip_prefix("\\u{")
+                    .and_then(|rest| rest.strip_suffix('}'))
+                    .filter(|hex| !hex.is_empty() && hex.len() <= 6)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or(LexError::InvalidEscape(escape))
+            },
+            _ => Err(LexError::InvalidEscape(format!("\\{}", c))),
+        }
+    }
+
+    fn read_type_value(&mut self) -> TokenKind {
+        let identifier = self.read_identifier();
+
+        // `String[...]`, `Integer[...]` and `Boolean[...]` are literals, read
+        // as a whole here rather than as separate tokens
+        if matches!(identifier.as_str(), "String" | "Integer" | "Boolean") && self.current_char == Some('[') {
+            let text = match self.read_raw_string(&identifier) {
+                Ok(text) => text,
+                Err(e) => return TokenKind::Error(e),
+            };
+
+            let invalid = || {
+                TokenKind::Error(LexError::InvalidLiteral {
+                    type_name: identifier.clone(),
+                    value: text.clone(),
+                })
+            };
+
+            return match identifier.as_str() {
+                "String" => TokenKind::StringLiteral(text),
+                "Integer" => match parse_integer(text.trim()) {
+                    Ok(number) => TokenKind::IntegerLiteral(number),
+                    Err(e) => TokenKind::Error(e),
+                },
+                _ => match text.trim() {
+                    "True" => TokenKind::BooleanLiteral(true),
+                    "False" => TokenKind::BooleanLiteral(false),
+                    _ => invalid(),
+                },
+            };
+        }
+
+        // The old parenthesized form is rejected outright. Its contents are
+        // still consumed so they don't turn into a cascade of other errors.
+        if matches!(identifier.as_str(), "String" | "Integer") && self.current_char == Some('(') {
+            return match self.read_string_literal() {
+                Some(_) => TokenKind::Error(LexError::ParenthesizedLiteral(identifier)),
+                None => TokenKind::Error(LexError::UnterminatedLiteral(identifier)),
+            };
+        }
+
+        // Keywords are only recognized once the whole word has been read, so
+        // names like 'island' or 'iffy' stay identifiers
         match identifier.as_str() {
-            "String" => Token::TypeString,
-            "Integer" => Token::TypeInteger,
-            "True" => Token::TypeTrue,
-            "False" => Token::TypeFalse,
-            "Unknown" => Token::TypeUnknown,
-            "if" => Token::If,
-            "func" => Token::Func,
-            _ => Token::Identifier(identifier),
+            "is" => self.read_is_or_is_not(),
+            "String" => TokenKind::TypeString,
+            "Integer" => TokenKind::TypeInteger,
+            "Boolean" => TokenKind::TypeBoolean,
+            "List" => TokenKind::TypeList,
+            "Map" => TokenKind::TypeMap,
+            "True" => TokenKind::BooleanLiteral(true),
+            "False" => TokenKind::BooleanLiteral(false),
+            "Unknown" => TokenKind::TypeUnknown,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "while" => TokenKind::While,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "to" => TokenKind::To,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "func" => TokenKind::Func,
+            "type" => TokenKind::Type,
+            "enum" => TokenKind::Enum,
+            "match" => TokenKind::Match,
+            "let" => TokenKind::Let,
+            "return" => TokenKind::Return,
+            "and" => TokenKind::And,
+            "or" => TokenKind::Or,
+            "not" => TokenKind::Not,
+            _ => TokenKind::Identifier(identifier),
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    // Called after "is" has been read. Looks past any whitespace for a
+    // following "not", and backs out again if there's some other word there.
+    fn read_is_or_is_not(&mut self) -> TokenKind {
+        let checkpoint = self.clone();
 
-        // Check for comment first, before the match statement to avoid borrow issues
-        if self.current_char == Some('/') && self.peek() == Some(&'/') {
-            let comment = self.read_comment();
-            return Token::Comment(comment);
+        self.skip_whitespace();
+        if self.current_char.is_some_and(|c| c.is_alphabetic()) && self.read_identifier() == "not" {
+            return TokenKind::NotEquals;
         }
 
-        match self.current_char {
-            None => Token::EOF,
+        *self = checkpoint;
+        TokenKind::Equals
+    }
 
-            Some('[') => {
-                self.advance();
-                Token::LeftBracket
-            },
+    pub fn next_token(&mut self) -> Token {
+        // The line the previous token ended on
+        let previous_line = self.line;
 
-            Some(']') => {
-                self.advance();
-                Token::RightBracket
-            },
+        // Block comments, and characters skipped in lenient mode, leave no
+        // token behind, so keep going until something produces one
+        loop {
+            self.skip_whitespace();
 
-            Some('{') => {
-                self.advance();
-                Token::LeftBrace
-            },
+            let start = self.position;
+            let line = self.line;
+            let column = self.column;
 
-            Some('}') => {
-                self.advance();
-                Token::RightBrace
-            },
+            // Statements end at the end of a line, so whatever came before
+            // can't be the left side of a subtraction
+            if line > previous_line {
+                self.after_operand = false;
+            }
 
-            Some('(') => {
-                self.advance();
-                Token::LeftParen
-            },
+            // Check for comment first, before the match statement to avoid borrow issues
+            let rest = &self.input[self.position..];
+            let kind = if rest.starts_with("/*") {
+                // Block comments leave no token behind, so they can sit anywhere
+                match self.skip_block_comment() {
+                    Ok(()) => continue,
+                    Err(e) => TokenKind::Error(e),
+                }
+            } else if rest.starts_with("///") && !rest.starts_with("////") {
+                // Only the single space after '///' is dropped, so indentation
+                // inside the doc text is kept
+                let comment = self.read_comment();
+                let doc = &comment[1..];
+                TokenKind::DocComment(doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string())
+            } else if rest.starts_with("//") {
+                let comment = self.read_comment();
+                TokenKind::Comment(comment.trim().to_string())
+            } else {
+                match self.current_char {
+                    None => TokenKind::Eof,
 
-            Some(')') => {
-                self.advance();
-                Token::RightParen
-            },
+                    Some('[') => {
+                        self.advance();
+                        TokenKind::LeftBracket
+                    },
+
+                    Some(']') => {
+                        self.advance();
+                        TokenKind::RightBracket
+                    },
+
+                    Some('{') => {
+                        self.advance();
+                        TokenKind::LeftBrace
+                    },
+
+                    Some('}') => {
+                        self.advance();
+                        TokenKind::RightBrace
+                    },
+
+                    Some('(') => {
+                        self.advance();
+                        TokenKind::LeftParen
+                    },
+
+                    Some(')') => {
+                        self.advance();
+                        TokenKind::RightParen
+                    },
+
+                    Some(':') => {
+                        self.advance();
+                        TokenKind::Colon
+                    },
+
+                    Some('=') => {
+                        self.advance();
+                        TokenKind::Assign
+                    },
+
+                    Some('+') => {
+                        self.advance();
+                        TokenKind::Plus
+                    },
+
+                    Some('-') if !self.after_operand && self.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                        self.read_number()
+                    },
+
+                    Some('-') => {
+                        self.advance();
+                        TokenKind::Minus
+                    },
+
+                    Some('*') => {
+                        self.advance();
+                        TokenKind::Star
+                    },
+
+                    Some('/') => {
+                        self.advance();
+                        TokenKind::Slash
+                    },
+
+                    Some('%') => {
+                        self.advance();
+                        TokenKind::Percent
+                    },
 
-            Some('i') => {
-                self.advance(); // consume 'i'
-                
-                // Check if it's "is"
-                if self.current_char == Some('s') {
-                    self.advance(); // consume 's'
-                    
-                    // Check if it's "is not"
-                    if self.current_char == Some(' ') {
-                        self.advance(); // consume space
-                        
-                        // Try to match "not"
-                        if self.current_char == Some('n') {
-                            self.advance(); // consume 'n'
-                            
-                            if self.current_char == Some('o') {
-                                self.advance(); // consume 'o'
-                                
-                                if self.current_char == Some('t') {
-                                    self.advance(); // consume 't'
-                                    return Token::NotEquals;
-                                }
-                            }
+                    Some('<') => {
+                        self.advance();
+                        if self.current_char == Some('=') {
+                            self.advance();
+                            TokenKind::LessEqual
+                        } else {
+                            TokenKind::Less
                         }
-                    }
-                    
-                    return Token::Equals;
-                }
-                
-                // If it's not "is" or "is not", treat 'i' as an identifier
-                let mut identifier = String::from("i");
-                while let Some(c) = self.current_char {
-                    if c.is_alphanumeric() || c == '_' {
-                        identifier.push(c);
+                    },
+
+                    Some('>') => {
                         self.advance();
-                    } else {
-                        break;
-                    }
-                }
-                
-                match identifier.as_str() {
-                    "if" => Token::If,
-                    _ => Token::Identifier(identifier),
-                }
-            },
-            
-            Some(':') => {
-                self.advance();
-                Token::Colon
-            },
-            
-            Some(',') => {
-                self.advance();
-                Token::Comma
-            },
+                        if self.current_char == Some('=') {
+                            self.advance();
+                            TokenKind::GreaterEqual
+                        } else {
+                            TokenKind::Greater
+                        }
+                    },
 
-            Some(c) if c.is_alphabetic() => {
-                self.read_type_value()
-            },
+                    Some(',') => {
+                        self.advance();
+                        TokenKind::Comma
+                    },
 
-            Some(c) if c.is_digit(10) => {
-                let number = self.read_number();
-                Token::IntegerLiteral(number)
-            },
+                    Some('.') => {
+                        self.advance();
+                        TokenKind::Dot
+                    },
 
-            Some(_) => {
-                self.advance();
-                self.next_token()
+                    Some(c) if c.is_alphabetic() => {
+                        self.read_type_value()
+                    },
+
+                    Some(c) if c.is_ascii_digit() => self.read_number(),
+
+                    Some(_) if self.lenient => {
+                        self.advance();
+                        continue;
+                    },
+
+                    Some(c) => {
+                        self.advance();
+                        TokenKind::Error(LexError::UnexpectedCharacter(c))
+                    },
+                }
+            };
+
+            if !matches!(kind, TokenKind::Comment(_) | TokenKind::DocComment(_)) {
+                self.after_operand = matches!(
+                    kind,
+                    TokenKind::Identifier(_)
+                        | TokenKind::StringLiteral(_)
+                        | TokenKind::IntegerLiteral(_)
+                        | TokenKind::BooleanLiteral(_)
+                        | TokenKind::RightParen
+                        | TokenKind::RightBracket
+                );
             }
+
+            let mut token = Token::new(kind, Span::new(start, self.position, line, column));
+            token.starts_line = line > previous_line;
+            return token;
         }
     }
 
@@ -298,7 +724,7 @@ impl<'a> Lexer<'a> {
         loop {
             let token = self.next_token();
 
-            if token == Token::EOF {
+            if token.kind == TokenKind::Eof {
                 tokens.push(token);
                 break;
             }
@@ -309,4 +735,329 @@ impl<'a> Lexer<'a> {
 
         tokens
     }
-}
\ No newline at end of file
+}
+// Parses the text of an integer literal: an optional '-', an optional 0x, 0o
+// or 0b prefix, then digits that may be separated by '_'
+fn parse_integer(text: &str) -> Result<i64, LexError> {
+    let invalid = || LexError::InvalidLiteral {
+        type_name: "Integer".to_string(),
+        value: text.to_string(),
+    };
+
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text),
+    };
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x") => (16, &unsigned[2..]),
+        Some("0o") => (8, &unsigned[2..]),
+        Some("0b") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+
+    // Separators may only come after the first digit
+    if !digits.chars().next().is_some_and(|c| c.is_digit(radix)) {
+        return Err(invalid());
+    }
+
+    let digits = digits.replace('_', "");
+    i64::from_str_radix(&format!("{}{}", sign, digits), radix).map_err(|e| match e.kind() {
+        std::num::IntErrorKind::PosOverflow | std::num::IntErrorKind::NegOverflow => {
+            LexError::IntegerOutOfRange(text.to_string())
+        },
+        _ => invalid(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The kinds of every token before Eof
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        let mut tokens = Lexer::new(source).tokenize();
+        assert_eq!(tokens.pop().map(|token| token.kind), Some(TokenKind::Eof));
+        tokens.into_iter().map(|token| token.kind).collect()
+    }
+
+    fn string(text: &str) -> TokenKind {
+        TokenKind::StringLiteral(text.to_string())
+    }
+
+    fn invalid_escape(escape: &str) -> TokenKind {
+        TokenKind::Error(LexError::InvalidEscape(escape.to_string()))
+    }
+
+    #[test]
+    fn string_literals_are_raw_text() {
+        assert_eq!(kinds("String[Hello, World!]"), vec![string("Hello, World!")]);
+        assert_eq!(kinds("String[]"), vec![string("")]);
+        assert_eq!(kinds("String[  padded  ]"), vec![string("  padded  ")]);
+    }
+
+    #[test]
+    fn string_literal_brackets_nest() {
+        assert_eq!(kinds("String[a[b[c]]d]"), vec![string("a[b[c]]d")]);
+        assert_eq!(kinds("String[[]]"), vec![string("[]")]);
+    }
+
+    #[test]
+    fn string_literal_escapes() {
+        assert_eq!(kinds(r"String[a\]b]"), vec![string("a]b")]);
+        assert_eq!(kinds(r"String[a\[b]"), vec![string("a[b")]);
+        assert_eq!(kinds(r"String[\\]"), vec![string("\\")]);
+        assert_eq!(kinds(r"String[a\nb\tc]"), vec![string("a\nb\tc")]);
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(kinds(r"String[\u{41}]"), vec![string("A")]);
+        assert_eq!(kinds(r"String[\u{1F600}]"), vec![string("\u{1F600}")]);
+        assert_eq!(kinds(r"String[\u{10FFFF}]"), vec![string("\u{10FFFF}")]);
+        assert_eq!(kinds(r"String[\u{000041}]"), vec![string("A")]);
+    }
+
+    #[test]
+    fn invalid_unicode_escapes() {
+        assert_eq!(kinds(r"String[\u{110000}]"), vec![invalid_escape(r"\u{110000}")]);
+        assert_eq!(kinds(r"String[\u{D800}]"), vec![invalid_escape(r"\u{D800}")]);
+        assert_eq!(kinds(r"String[\u{}]"), vec![invalid_escape(r"\u{}")]);
+        assert_eq!(kinds(r"String[\u{0000041}]"), vec![invalid_escape(r"\u{0000041}")]);
+        assert_eq!(kinds(r"String[\u41]"), vec![invalid_escape(r"\u")]);
+    }
+
+    #[test]
+    fn unclosed_unicode_escape_keeps_the_closing_bracket() {
+        assert_eq!(
+            kinds(r"String[\u{41] x"),
+            vec![invalid_escape(r"\u{41"), TokenKind::Identifier("x".to_string())]
+        );
+    }
+
+    #[test]
+    fn unknown_escape_is_reported_once_per_literal() {
+        assert_eq!(
+            kinds(r"String[\q \z] x"),
+            vec![invalid_escape(r"\q"), TokenKind::Identifier("x".to_string())]
+        );
+    }
+
+    #[test]
+    fn unterminated_literals() {
+        assert_eq!(
+            kinds("String[abc"),
+            vec![TokenKind::Error(LexError::UnterminatedLiteral("String".to_string()))]
+        );
+        assert_eq!(
+            kinds(r"String[abc\]"),
+            vec![TokenKind::Error(LexError::UnterminatedLiteral("String".to_string()))]
+        );
+    }
+
+    #[test]
+    fn parenthesized_literals_are_rejected() {
+        assert_eq!(
+            kinds("String(abc) x"),
+            vec![
+                TokenKind::Error(LexError::ParenthesizedLiteral("String".to_string())),
+                TokenKind::Identifier("x".to_string()),
+            ]
+        );
+    }
+
+    fn invalid_integer(text: &str) -> LexError {
+        LexError::InvalidLiteral {
+            type_name: "Integer".to_string(),
+            value: text.to_string(),
+        }
+    }
+
+    fn out_of_range(text: &str) -> LexError {
+        LexError::IntegerOutOfRange(text.to_string())
+    }
+
+    #[test]
+    fn decimal_integers() {
+        assert_eq!(parse_integer("0"), Ok(0));
+        assert_eq!(parse_integer("42"), Ok(42));
+        assert_eq!(parse_integer("-7"), Ok(-7));
+        assert_eq!(parse_integer("007"), Ok(7));
+    }
+
+    #[test]
+    fn integer_prefixes() {
+        assert_eq!(parse_integer("0xFF"), Ok(255));
+        assert_eq!(parse_integer("0xff"), Ok(255));
+        assert_eq!(parse_integer("0o17"), Ok(15));
+        assert_eq!(parse_integer("0b1010"), Ok(10));
+        assert_eq!(parse_integer("-0x10"), Ok(-16));
+    }
+
+    #[test]
+    fn integer_separators() {
+        assert_eq!(parse_integer("1_000_000"), Ok(1_000_000));
+        assert_eq!(parse_integer("0xFF_FF"), Ok(0xFFFF));
+        assert_eq!(parse_integer("0b1_0"), Ok(2));
+        assert_eq!(parse_integer("1_"), Ok(1));
+    }
+
+    #[test]
+    fn separator_must_follow_a_digit() {
+        assert_eq!(parse_integer("_1"), Err(invalid_integer("_1")));
+        assert_eq!(parse_integer("-_1"), Err(invalid_integer("-_1")));
+        assert_eq!(parse_integer("0x_FF"), Err(invalid_integer("0x_FF")));
+    }
+
+    #[test]
+    fn malformed_integers() {
+        assert_eq!(parse_integer(""), Err(invalid_integer("")));
+        assert_eq!(parse_integer("-"), Err(invalid_integer("-")));
+        assert_eq!(parse_integer("0x"), Err(invalid_integer("0x")));
+        assert_eq!(parse_integer("0b102"), Err(invalid_integer("0b102")));
+        assert_eq!(parse_integer("12abc"), Err(invalid_integer("12abc")));
+        assert_eq!(parse_integer("+5"), Err(invalid_integer("+5")));
+        assert_eq!(parse_integer("--5"), Err(invalid_integer("--5")));
+        assert_eq!(parse_integer("0x-5"), Err(invalid_integer("0x-5")));
+    }
+
+    #[test]
+    fn integer_bounds() {
+        assert_eq!(parse_integer("9223372036854775807"), Ok(i64::MAX));
+        assert_eq!(parse_integer("-9223372036854775808"), Ok(i64::MIN));
+        assert_eq!(parse_integer("-0x8000000000000000"), Ok(i64::MIN));
+        assert_eq!(parse_integer("9223372036854775808"), Err(out_of_range("9223372036854775808")));
+        assert_eq!(parse_integer("-9223372036854775809"), Err(out_of_range("-9223372036854775809")));
+        assert_eq!(parse_integer("0x8000000000000000"), Err(out_of_range("0x8000000000000000")));
+    }
+
+    #[test]
+    fn integer_literals_in_brackets() {
+        assert_eq!(kinds("Integer[ -1_000 ]"), vec![TokenKind::IntegerLiteral(-1000)]);
+        assert_eq!(kinds("Integer[0b11]"), vec![TokenKind::IntegerLiteral(3)]);
+        assert_eq!(kinds("Integer[abc]"), vec![TokenKind::Error(invalid_integer("abc"))]);
+    }
+
+    #[test]
+    fn bare_integers_take_the_whole_word() {
+        assert_eq!(kinds("12abc"), vec![TokenKind::Error(invalid_integer("12abc"))]);
+        assert_eq!(kinds("-9223372036854775808"), vec![TokenKind::IntegerLiteral(i64::MIN)]);
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(
+            kinds("a /* outer /* inner */ still outer */ b"),
+            vec![TokenKind::Identifier("a".to_string()), TokenKind::Identifier("b".to_string())]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        assert_eq!(
+            kinds("a /* /* */"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Error(LexError::UnterminatedComment),
+            ]
+        );
+    }
+
+    #[test]
+    fn many_block_comments_in_a_row() {
+        let source = "/* a */".repeat(100_000) + "b";
+        assert_eq!(kinds(&source), vec![TokenKind::Identifier("b".to_string())]);
+    }
+
+    #[test]
+    fn unexpected_characters() {
+        assert_eq!(
+            kinds("a @ b"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Error(LexError::UnexpectedCharacter('@')),
+                TokenKind::Identifier("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_lexer_skips_unexpected_characters() {
+        let source = "a ".to_string() + &"@".repeat(300_000) + " b";
+        let tokens: Vec<TokenKind> = Lexer::new(&source)
+            .lenient(true)
+            .tokenize()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn minus_after_an_operand_is_subtraction() {
+        assert_eq!(
+            kinds("a -1"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Minus,
+                TokenKind::IntegerLiteral(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn minus_starting_a_line_is_a_negative_literal() {
+        let tokens = Lexer::new("a\n-1").tokenize();
+        assert_eq!(tokens[1].kind, TokenKind::IntegerLiteral(-1));
+        assert!(!tokens[0].starts_line);
+        assert!(tokens[1].starts_line);
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_eq!(
+            kinds("///  indented\n//// plain"),
+            vec![
+                TokenKind::DocComment(" indented".to_string()),
+                TokenKind::Comment("// plain".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn words_starting_with_is_are_identifiers() {
+        for word in ["isValid", "island", "issue"] {
+            assert_eq!(kinds(word), vec![TokenKind::Identifier(word.to_string())]);
+        }
+    }
+
+    #[test]
+    fn is_not_spans_any_whitespace() {
+        assert_eq!(
+            kinds("a is  not b"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::NotEquals,
+                TokenKind::Identifier("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn is_before_a_word_starting_with_not() {
+        assert_eq!(
+            kinds("a is notable"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Equals,
+                TokenKind::Identifier("notable".to_string()),
+            ]
+        );
+    }
+}
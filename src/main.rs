@@ -1,66 +1,180 @@
+mod span;
+mod diagnostic;
 mod lexer;
 mod parser;
 mod interpreter;
 mod typechecker;
+mod repl;
 
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process::ExitCode;
+use std::thread;
+
+use crate::diagnostic::render_all;
 use crate::lexer::Lexer;
 use crate::parser::Parser;
 use crate::interpreter::Interpreter;
 use crate::typechecker::TypeChecker;
+use crate::repl::Repl;
+
+const USAGE: &str = "\
+usage: noam [command] [--lenient] <script.noam | ->
+       noam repl
+
+commands:
+    run       lex, parse, type check and interpret the script (default)
+    check     stop after type checking
+    ast       print the parsed syntax tree
+    tokens    print the tokens produced by the lexer
+    repl      start an interactive session
+
+options:
+    --lenient skip characters the lexer doesn't recognize instead of
+              reporting them";
+
+// The parser, checker and interpreter all recurse, and the interpreter allows
+// calls to nest `MAX_CALL_DEPTH` deep, so they run on a thread with a stack
+// large enough for that. Only the part actually used is ever committed.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
-fn main() {
-    let input = r#"
-        // Define a custom function to greet someone
-        func greet(name: String) {
-            print(String[Hello], name)
+// The pipeline stage a command stops after
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Tokens,
+    Ast,
+    Check,
+    Run,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "tokens" => Some(Command::Tokens),
+            "ast" => Some(Command::Ast),
+            "check" => Some(Command::Check),
+            "run" => Some(Command::Run),
+            _ => None,
         }
+    }
+}
 
-        // Call our custom function
-        greet(String[World])
+fn main() -> ExitCode {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(start)
+        .expect("failed to start the main thread")
+        .join()
+        .unwrap_or(ExitCode::FAILURE)
+}
 
-        // Test conditional execution with "is" operator
-        if String[Hello] is String[Hello] {
-            print(String[True])
+fn start() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let lenient = args.iter().any(|arg| arg == "--lenient");
+    args.retain(|arg| arg != "--lenient");
+
+    if let [name] = args.as_slice()
+        && name == "repl"
+    {
+        return match Repl::new().run() {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let (command, path) = match args.as_slice() {
+        [name, path] => match Command::from_name(name) {
+            Some(command) => (command, path),
+            None => {
+                eprintln!("error: unknown command '{}'\n\n{}", name, USAGE);
+                return ExitCode::from(2);
+            }
+        },
+        [path] if Command::from_name(path).is_none() => (Command::Run, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
         }
+    };
 
-        // Test conditional execution with "is not" operator
-        if String[Hello] is not String[Goodbye] {
-            print(String[Different])
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("error: could not read '{}': {}", path, e);
+            return ExitCode::from(2);
         }
+    };
 
-        // Use the built-in function
-        print(String[Answer], Integer[42])
-    "#;
+    match run(command, &source, display_name(path), lenient) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(report) => {
+            eprintln!("{}", report);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let mut lexer = Lexer::new(input);
+// Reads the script from the given path, or from stdin when the path is "-"
+fn read_source(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+fn display_name(path: &str) -> &str {
+    if path == "-" { "<stdin>" } else { path }
+}
+
+// Runs the Lexer -> Parser -> TypeChecker -> Interpreter pipeline up to
+// the stage selected by the command. Errors come back already formatted.
+fn run(command: Command, source: &str, file_name: &str, lenient: bool) -> Result<(), String> {
+    let mut lexer = Lexer::new(source).lenient(lenient);
     let tokens = lexer.tokenize();
 
-    println!("Tokens:");
-    for token in &tokens {
-        println!("{:?}", token);
+    if command == Command::Tokens {
+        for token in &tokens {
+            println!("{:<8} {:?}", token.span.to_string(), token.kind);
+        }
+        return Ok(());
     }
 
-    println!("\nParsing AST:");
     let mut parser = Parser::new(tokens);
-    match parser.parse() {
-        Ok(ast) => {
-            println!("{:#?}", ast);
-            
-            println!("\nType checking program:");
-            let mut type_checker = TypeChecker::new();
-            match type_checker.check_program(&ast) {
-                Ok(_) => {
-                    println!("Type checking successful");
-                    println!("\nInterpreting program:");
-                    let mut interpreter = Interpreter::new();
-                    match interpreter.interpret(ast) {
-                        Ok(_) => println!("Program executed successfully"),
-                        Err(e) => println!("Runtime error: {}", e),
-                    }
-                },
-                Err(e) => println!("Type error: {}", e),
-            }
-        },
-        Err(e) => println!("Parsing error: {}", e),
+    let (ast, errors) = parser.parse();
+
+    // The ast command still shows whatever could be parsed
+    if command == Command::Ast {
+        println!("{:#?}", ast);
+    }
+
+    if !errors.is_empty() {
+        return Err(render_all(&errors, file_name, source));
+    }
+
+    if command == Command::Ast {
+        return Ok(());
     }
+
+    let mut type_checker = TypeChecker::new();
+    type_checker
+        .check_program(&ast)
+        .map_err(|errors| render_all(&errors, file_name, source))?;
+
+    if command == Command::Check {
+        return Ok(());
+    }
+
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .interpret(ast)
+        .map(|_| ())
+        .map_err(|e| e.render(file_name, source))
 }
@@ -1,44 +1,205 @@
-use crate::lexer::Token;
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::lexer::{LexError, Token, TokenKind};
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Lex(LexError),
+    Expected {
+        expected: String,
+        found: TokenKind,
+    },
+    UnexpectedToken(TokenKind),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Lex(e) => write!(f, "{}", e),
+            ParseError::Expected { expected, found } => {
+                write!(f, "Expected {}, found {}", expected, found)
+            },
+            ParseError::UnexpectedToken(found) => write!(f, "Unexpected {}", found),
+        }
+    }
+}
+
+impl ErrorCode for ParseError {
+    fn code(&self) -> &'static str {
+        match self {
+            ParseError::Lex(e) => e.code(),
+            ParseError::Expected { .. } => "E0201",
+            ParseError::UnexpectedToken(_) => "E0202",
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     StringLiteral(String),
     IntegerLiteral(i64),
+    BooleanLiteral(bool),
     Identifier(String),
     FunctionCall {
         name: String,
         arguments: Vec<Expression>,
     },
-    TypedValue {
-        type_name: String,
-        value: Box<Expression>,
-    },
     BinaryOperation {
         left: Box<Expression>,
         operator: String,
         right: Box<Expression>,
     },
+    UnaryOperation {
+        operator: String,
+        operand: Box<Expression>,
+    },
+    RecordLiteral {
+        type_name: String,
+        fields: Vec<FieldValue>,
+    },
+    FieldAccess {
+        target: Box<Expression>,
+        field: String,
+    },
+    ListLiteral(Vec<Expression>),
+    MapLiteral(Vec<(Expression, Expression)>),
+    Index {
+        target: Box<Expression>,
+        index: Box<Expression>,
+    },
+    // Stands in for a token the lexer couldn't read. Its error has already
+    // been reported, so the rest of the statement still gets parsed.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub enum Statement {
+#[allow(clippy::enum_variant_names)]
+pub enum StatementKind {
     Expression(Expression),
     FunctionDeclaration {
         name: String,
         parameters: Vec<Parameter>,
+        return_type: Option<TypeName>,
         body: Vec<Statement>,
+        // The '///' lines written directly above the function
+        doc: Option<String>,
     },
     IfStatement {
         condition: Expression,
         body: Vec<Statement>,
+        // An 'else if' is an else body holding a single IfStatement
+        else_body: Option<Vec<Statement>>,
+    },
+    WhileStatement {
+        condition: Expression,
+        body: Vec<Statement>,
     },
+    // `for x in iterable` walks a list or a map's keys. With an `end` it is
+    // `for i in iterable to end`, counting up from the iterable to just
+    // before the end.
+    ForStatement {
+        variable: String,
+        iterable: Expression,
+        end: Option<Expression>,
+        body: Vec<Statement>,
+    },
+    Break,
+    Continue,
+    VariableDeclaration {
+        name: String,
+        type_name: Option<TypeName>,
+        value: Expression,
+    },
+    Assignment {
+        name: String,
+        value: Expression,
+    },
+    // `name.field.other = value`, with the path of fields after the name
+    FieldAssignment {
+        name: String,
+        fields: Vec<String>,
+        value: Expression,
+    },
+    TypeDeclaration {
+        name: String,
+        fields: Vec<Parameter>,
+    },
+    EnumDeclaration {
+        name: String,
+        variants: Vec<Variant>,
+    },
+    Match {
+        value: Expression,
+        arms: Vec<MatchArm>,
+    },
+    Return(Option<Expression>),
+    #[allow(dead_code)]
     Comment(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
-    pub type_name: String,
+    pub type_name: TypeName,
+    pub span: Span,
+}
+
+// A type as written in an annotation, such as `Map[String, List[Integer]]`
+#[derive(Debug, Clone)]
+pub struct TypeName {
+    pub kind: TypeNameKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeNameKind {
+    String,
+    Integer,
+    Boolean,
+    Unknown,
+    List(Box<TypeName>),
+    Map(Box<TypeName>, Box<TypeName>),
+    // A record or enum declared by the script
+    Named(String),
+}
+
+// One `field: value` pair in a record literal such as `Point[x: 1, y: 2]`
+#[derive(Debug, Clone)]
+pub struct FieldValue {
+    pub name: String,
+    pub value: Expression,
+    pub span: Span,
+}
+
+// One variant of an enum, with the fields of its payload
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Parameter>,
+    pub span: Span,
+}
+
+// `Circle[radius: r] { ... }` in a match. The variant is None for the
+// catch-all `else` arm, and each binding is a payload field and the name
+// it is bound to in the body.
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub variant: Option<String>,
+    pub bindings: Vec<(String, String)>,
+    pub body: Vec<Statement>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -46,315 +207,1143 @@ pub struct Program {
     pub statements: Vec<Statement>,
 }
 
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression { kind, span }
+    }
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current_position: usize,
+    errors: Vec<Diagnostic<ParseError>>,
 }
 
 impl Parser {
+    // Error tokens stay in the stream and are reported when the parser
+    // reaches them, so each error is reported where it happened
     pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
             tokens,
             current_position: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Result<Program, String> {
+    // Parses as much of the program as possible. Statements that fail to
+    // parse are left out of the returned program and reported instead.
+    pub fn parse(&mut self) -> (Program, Vec<Diagnostic<ParseError>>) {
         let mut program = Program {
             statements: Vec::new(),
         };
 
         while !self.is_at_end() {
+            let start = self.current_position;
             match self.parse_statement() {
                 Ok(statement) => program.statements.push(statement),
-                Err(e) => return Err(e),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize(start);
+
+                    // A stray '}' can't start a statement, so step over it
+                    self.match_token(&TokenKind::RightBrace);
+                }
             }
         }
 
-        Ok(program)
+        (program, std::mem::take(&mut self.errors))
     }
 
-    fn parse_statement(&mut self) -> Result<Statement, String> {
+    fn lex_error(error: LexError, span: Span) -> Diagnostic<ParseError> {
+        let diagnostic = Diagnostic::new(ParseError::Lex(error.clone()), span);
+
+        match error {
+            LexError::UnterminatedLiteral(_) => {
+                diagnostic.with_note("a literal's opening bracket must have a matching closing bracket")
+            },
+            LexError::ParenthesizedLiteral(type_name) => {
+                diagnostic.with_note(format!("write {}[...] instead", type_name))
+            },
+            LexError::InvalidLiteral { type_name, .. } if type_name == "Boolean" => {
+                diagnostic.with_note("a Boolean literal is either True or False")
+            },
+            LexError::InvalidLiteral { .. } => diagnostic.with_note(
+                "integers are written as digits, with an optional '-', '_' separators and a 0x, 0o or 0b prefix",
+            ),
+            LexError::IntegerOutOfRange(_) => diagnostic.with_note(format!(
+                "integers must be between {} and {}",
+                i64::MIN,
+                i64::MAX
+            )),
+            LexError::UnexpectedCharacter(';') => {
+                diagnostic.with_note("statements end at the end of a line, no ';' is needed")
+            },
+            LexError::UnexpectedCharacter(_) => diagnostic,
+            LexError::UnterminatedComment => {
+                diagnostic.with_note("block comments nest, so every '/*' needs its own '*/'")
+            },
+            LexError::InvalidEscape(_) => diagnostic.with_note(
+                "valid escapes are \\], \\[, \\\\, \\n, \\t and \\u{...}",
+            ),
+        }
+    }
+
+    // Skips tokens until one that can start or end a statement, so we can
+    // carry on parsing after an error. A token starting a line also starts a
+    // statement, unless the failed statement began there and we'd never move.
+    fn synchronize(&mut self, statement_start: usize) {
+        while !self.is_at_end() {
+            let token = self.peek_token();
+            if token.starts_line && self.current_position > statement_start {
+                return;
+            }
+
+            match token.kind {
+                TokenKind::Func
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Type
+                | TokenKind::Enum
+                | TokenKind::Match
+                | TokenKind::Let
+                | TokenKind::Return
+                | TokenKind::RightBrace => return,
+                _ => {
+                    // Report lex errors being skipped over, unless one of them
+                    // is what stopped the statement in the first place
+                    let token = self.advance();
+                    if let TokenKind::Error(e) = token.kind
+                        && !self.errors.last().is_some_and(|last| last.span == token.span)
+                    {
+                        self.errors.push(Self::lex_error(e, token.span));
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
         let token = self.peek_token();
-        
-        match token {
-            Token::Func => self.parse_function_declaration(),
-            Token::If => self.parse_if_statement(),
-            Token::Comment(comment) => {
+
+        match token.kind {
+            TokenKind::Func => self.parse_function_declaration(),
+            TokenKind::If => self.parse_if_statement(),
+            TokenKind::While => self.parse_while_statement(),
+            TokenKind::For => self.parse_for_statement(),
+            TokenKind::Break => {
+                self.advance();
+                Ok(Statement::new(StatementKind::Break, token.span))
+            },
+            TokenKind::Continue => {
+                self.advance();
+                Ok(Statement::new(StatementKind::Continue, token.span))
+            },
+            TokenKind::Let => self.parse_variable_declaration(),
+            TokenKind::Return => self.parse_return_statement(),
+            TokenKind::Type => self.parse_type_declaration(),
+            TokenKind::Enum => self.parse_enum_declaration(),
+            TokenKind::Match => self.parse_match_statement(),
+            TokenKind::Identifier(_) if self.is_assignment() => self.parse_assignment(),
+            TokenKind::Comment(comment) => {
                 self.advance();
-                Ok(Statement::Comment(comment))
+                Ok(Statement::new(StatementKind::Comment(comment), token.span))
+            },
+            TokenKind::DocComment(_) if self.documents_function() => self.parse_documented_function(),
+            // A doc comment with no function after it is just a comment
+            TokenKind::DocComment(comment) => {
+                self.advance();
+                Ok(Statement::new(StatementKind::Comment(comment), token.span))
             },
             _ => {
                 let expr = self.parse_expression()?;
-                Ok(Statement::Expression(expr))
+                let span = expr.span;
+                Ok(Statement::new(StatementKind::Expression(expr), span))
             }
         }
     }
 
-    fn parse_function_declaration(&mut self) -> Result<Statement, String> {
+    // Whether the doc comment at the current token leads up to a function
+    fn documents_function(&self) -> bool {
+        let next = (0..)
+            .map(|offset| self.peek_token_at(offset).kind)
+            .find(|kind| !matches!(kind, TokenKind::DocComment(_) | TokenKind::Comment(_)));
+        next == Some(TokenKind::Func)
+    }
+
+    // Collects the '///' lines in front of a function and attaches them to it
+    fn parse_documented_function(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        let mut lines = Vec::new();
+
+        loop {
+            match self.peek_token().kind {
+                TokenKind::DocComment(line) => lines.push(line),
+                // Plain comments may sit between the doc and the function
+                TokenKind::Comment(_) => {},
+                _ => break,
+            }
+            self.advance();
+        }
+
+        let mut statement = self.parse_function_declaration()?;
+        if let StatementKind::FunctionDeclaration { doc, .. } = &mut statement.kind {
+            *doc = Some(lines.join("\n"));
+        }
+
+        Ok(statement)
+    }
+
+    fn parse_function_declaration(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
         // Consume 'func' token
-        self.advance();
-        
+        let start = self.advance().span;
+
         // Get function name
-        let name = match self.consume_token() {
-            Token::Identifier(name) => name,
-            _ => return Err("Expected function name after 'func' keyword".to_string()),
+        let name = match self.peek_token().kind {
+            TokenKind::Identifier(name) => {
+                self.advance();
+                name
+            },
+            _ => return Err(self.error("function name after 'func' keyword")),
         };
-        
+
         // Consume opening parenthesis
-        if !self.match_token(&Token::LeftParen) {
-            return Err("Expected '(' after function name".to_string());
+        if !self.match_token(&TokenKind::LeftParen) {
+            return Err(self.error("'(' after function name"));
         }
-        
+
         // Parse parameters
         let parameters = self.parse_parameters()?;
-        
+
         // Consume closing parenthesis
-        if !self.match_token(&Token::RightParen) {
-            return Err("Expected ')' after parameters".to_string());
+        if !self.match_token(&TokenKind::RightParen) {
+            return Err(self.error("')' after parameters"));
         }
-        
-        // Consume opening brace
-        if !self.match_token(&Token::LeftBrace) {
-            return Err("Expected '{' after function declaration".to_string());
-        }
-        
+
+        // Functions without a declared return type return nothing
+        let return_type = if self.match_token(&TokenKind::Colon) {
+            Some(self.parse_type_name()?)
+        } else {
+            None
+        };
+
         // Parse function body
-        let mut body = Vec::new();
-        while !self.check(&Token::RightBrace) && !self.is_at_end() {
-            let statement = self.parse_statement()?;
-            body.push(statement);
-        }
-        
-        // Consume closing brace
-        if !self.match_token(&Token::RightBrace) {
-            return Err("Expected '}' after function body".to_string());
-        }
-        
-        Ok(Statement::FunctionDeclaration {
-            name,
-            parameters,
-            body,
-        })
+        let body = self.parse_block("function declaration", "function body")?;
+
+        Ok(Statement::new(
+            StatementKind::FunctionDeclaration {
+                name,
+                parameters,
+                return_type,
+                body,
+                doc: None,
+            },
+            start.to(self.previous_span()),
+        ))
     }
 
-    fn parse_parameters(&mut self) -> Result<Vec<Parameter>, String> {
+    fn parse_parameters(&mut self) -> Result<Vec<Parameter>, Diagnostic<ParseError>> {
         let mut parameters = Vec::new();
-        
+
         // If next token is ')', we have no parameters
-        if self.check(&Token::RightParen) {
+        if self.check(&TokenKind::RightParen) {
             return Ok(parameters);
         }
-        
+
         loop {
             // Get parameter name
-            let name = match self.consume_token() {
-                Token::Identifier(name) => name,
-                _ => return Err("Expected parameter name".to_string()),
+            let start = self.peek_token().span;
+            let name = match self.peek_token().kind {
+                TokenKind::Identifier(name) => {
+                    self.advance();
+                    name
+                },
+                _ => return Err(self.error("parameter name")),
             };
-            
+
             // Consume colon
-            if !self.match_token(&Token::Colon) {
-                return Err("Expected ':' after parameter name".to_string());
+            if !self.match_token(&TokenKind::Colon) {
+                return Err(self.error("':' after parameter name"));
             }
-            
+
             // Get parameter type
-            let type_name = match self.consume_token() {
-                Token::TypeString => "String".to_string(),
-                Token::TypeInteger => "Integer".to_string(),
-                Token::TypeUnknown => "Unknown".to_string(),
-                Token::Identifier(type_name) => type_name,
-                _ => return Err("Expected type name after ':'".to_string()),
-            };
-            
-            parameters.push(Parameter { name, type_name });
-            
+            let type_name = self.parse_type_name()?;
+
+            parameters.push(Parameter {
+                name,
+                type_name,
+                span: start.to(self.previous_span()),
+            });
+
             // If next token is ')', we're done
-            if self.check(&Token::RightParen) {
+            if self.check(&TokenKind::RightParen) {
                 break;
             }
-            
+
             // Otherwise, expect a comma
-            if !self.check(&Token::Comma) {
-                return Err("Expected ',' between parameters".to_string());
+            if !self.check(&TokenKind::Comma) {
+                return Err(self.error("',' between parameters"));
             }
-            
+
             // Consume comma
             self.advance();
         }
-        
+
         Ok(parameters)
     }
 
-    fn parse_if_statement(&mut self) -> Result<Statement, String> {
-        // Consume 'if' token
+    fn parse_type_name(&mut self) -> Result<TypeName, Diagnostic<ParseError>> {
+        let start = self.peek_token().span;
+        let kind = match self.peek_token().kind {
+            TokenKind::TypeString => TypeNameKind::String,
+            TokenKind::TypeInteger => TypeNameKind::Integer,
+            TokenKind::TypeBoolean => TypeNameKind::Boolean,
+            TokenKind::TypeUnknown => TypeNameKind::Unknown,
+            TokenKind::TypeList => {
+                self.advance();
+                if !self.match_token(&TokenKind::LeftBracket) {
+                    return Err(self.error("'[' after 'List'"));
+                }
+                let element_type = self.parse_type_name()?;
+                if !self.match_token(&TokenKind::RightBracket) {
+                    return Err(self.error("']' after list element type"));
+                }
+                return Ok(TypeName {
+                    kind: TypeNameKind::List(Box::new(element_type)),
+                    span: start.to(self.previous_span()),
+                });
+            },
+            TokenKind::TypeMap => {
+                self.advance();
+                if !self.match_token(&TokenKind::LeftBracket) {
+                    return Err(self.error("'[' after 'Map'"));
+                }
+                let key_type = self.parse_type_name()?;
+                if !self.match_token(&TokenKind::Comma) {
+                    return Err(self.error("',' between map key and value types"));
+                }
+                let value_type = self.parse_type_name()?;
+                if !self.match_token(&TokenKind::RightBracket) {
+                    return Err(self.error("']' after map value type"));
+                }
+                return Ok(TypeName {
+                    kind: TypeNameKind::Map(Box::new(key_type), Box::new(value_type)),
+                    span: start.to(self.previous_span()),
+                });
+            },
+            TokenKind::Identifier(type_name) => TypeNameKind::Named(type_name),
+            _ => return Err(self.error("type name after ':'")),
+        };
+        self.advance();
+
+        Ok(TypeName { kind, span: start })
+    }
+
+    fn parse_variable_declaration(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'let' token
+        let start = self.advance().span;
+
+        // Get variable name
+        let name = match self.peek_token().kind {
+            TokenKind::Identifier(name) => {
+                self.advance();
+                name
+            },
+            _ => return Err(self.error("variable name after 'let' keyword")),
+        };
+
+        // The type annotation is optional, without one the type is inferred
+        let type_name = if self.match_token(&TokenKind::Colon) {
+            Some(self.parse_type_name()?)
+        } else {
+            None
+        };
+
+        // Consume '='
+        if !self.match_token(&TokenKind::Assign) {
+            return Err(self.error("'=' after variable name"));
+        }
+
+        let value = self.parse_expression()?;
+        let span = start.to(value.span);
+
+        Ok(Statement::new(
+            StatementKind::VariableDeclaration { name, type_name, value },
+            span,
+        ))
+    }
+
+    // Whether the tokens ahead are `name =` or `name.field.other =`
+    fn is_assignment(&self) -> bool {
+        let mut offset = 1;
+        while self.peek_token_at(offset).kind == TokenKind::Dot
+            && matches!(self.peek_token_at(offset + 1).kind, TokenKind::Identifier(_))
+        {
+            offset += 2;
+        }
+
+        self.peek_token_at(offset).kind == TokenKind::Assign
+    }
+
+    fn parse_assignment(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        let token = self.advance();
+        let name = match token.kind {
+            TokenKind::Identifier(name) => name,
+            _ => unreachable!(),
+        };
+
+        // Collect the field path, which is_assignment has already checked
+        let mut fields = Vec::new();
+        while self.match_token(&TokenKind::Dot) {
+            if let TokenKind::Identifier(field) = self.advance().kind {
+                fields.push(field);
+            }
+        }
+
+        // Consume '=', which parse_statement has already seen
         self.advance();
-        
+
+        let value = self.parse_expression()?;
+        let span = token.span.to(value.span);
+
+        if fields.is_empty() {
+            Ok(Statement::new(StatementKind::Assignment { name, value }, span))
+        } else {
+            Ok(Statement::new(StatementKind::FieldAssignment { name, fields, value }, span))
+        }
+    }
+
+    fn parse_enum_declaration(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'enum' token
+        let start = self.advance().span;
+
+        // Get enum name
+        let name = match self.peek_token().kind {
+            TokenKind::Identifier(name) => {
+                self.advance();
+                name
+            },
+            _ => return Err(self.error("enum name after 'enum' keyword")),
+        };
+
+        let open = self.peek_token().span;
+        if !self.match_token(&TokenKind::LeftBrace) {
+            return Err(self.error("'{' after enum name"));
+        }
+
+        // Variants are separated by commas, and a payload is written like
+        // the fields of a record inside brackets
+        let mut variants = Vec::new();
+        while !self.check(&TokenKind::RightBrace) {
+            let variant_start = self.peek_token().span;
+            let variant_name = match self.peek_token().kind {
+                TokenKind::Identifier(variant_name) => {
+                    self.advance();
+                    variant_name
+                },
+                _ => return Err(self.error("variant name")),
+            };
+
+            let mut fields = Vec::new();
+            if self.match_token(&TokenKind::LeftBracket) {
+                fields = self.parse_fields(&TokenKind::RightBracket)?;
+                if !self.match_token(&TokenKind::RightBracket) {
+                    return Err(self.error("']' after variant fields"));
+                }
+            }
+
+            variants.push(Variant {
+                name: variant_name,
+                fields,
+                span: variant_start.to(self.previous_span()),
+            });
+
+            if !self.match_token(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        if !self.match_token(&TokenKind::RightBrace) {
+            return Err(self
+                .error("'}' after variants")
+                .with_label(open, "unclosed '{' opened here"));
+        }
+
+        Ok(Statement::new(
+            StatementKind::EnumDeclaration { name, variants },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    fn parse_match_statement(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'match' token
+        let start = self.advance().span;
+
+        let value = self.parse_expression()?;
+
+        let open = self.peek_token().span;
+        if !self.match_token(&TokenKind::LeftBrace) {
+            return Err(self.error("'{' after match value"));
+        }
+
+        let mut arms = Vec::new();
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            let arm_start = self.peek_token().span;
+            let variant = match self.peek_token().kind {
+                TokenKind::Else => None,
+                TokenKind::Identifier(variant) => Some(variant),
+                _ => return Err(self.error("variant name or 'else' in match arm")),
+            };
+            self.advance();
+
+            // `[field, other: name]` binds payload fields, optionally renamed
+            let mut bindings = Vec::new();
+            if variant.is_some() && self.match_token(&TokenKind::LeftBracket) {
+                while !self.check(&TokenKind::RightBracket) {
+                    let field = match self.peek_token().kind {
+                        TokenKind::Identifier(field) => {
+                            self.advance();
+                            field
+                        },
+                        _ => return Err(self.error("field name in pattern")),
+                    };
+
+                    let binding = if self.match_token(&TokenKind::Colon) {
+                        match self.peek_token().kind {
+                            TokenKind::Identifier(binding) => {
+                                self.advance();
+                                binding
+                            },
+                            _ => return Err(self.error("variable name after ':'")),
+                        }
+                    } else {
+                        field.clone()
+                    };
+                    bindings.push((field, binding));
+
+                    if !self.match_token(&TokenKind::Comma) {
+                        break;
+                    }
+                }
+
+                if !self.match_token(&TokenKind::RightBracket) {
+                    return Err(self.error("']' after pattern fields"));
+                }
+            }
+
+            let pattern_span = arm_start.to(self.previous_span());
+            let body = self.parse_block("match pattern", "match arm")?;
+
+            arms.push(MatchArm {
+                variant,
+                bindings,
+                body,
+                span: pattern_span,
+            });
+        }
+
+        if !self.match_token(&TokenKind::RightBrace) {
+            return Err(self
+                .error("'}' after match arms")
+                .with_label(open, "unclosed '{' opened here"));
+        }
+
+        Ok(Statement::new(
+            StatementKind::Match { value, arms },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    // Parses `name: Type` pairs separated by commas, up to `closing`
+    fn parse_fields(&mut self, closing: &TokenKind) -> Result<Vec<Parameter>, Diagnostic<ParseError>> {
+        let mut fields = Vec::new();
+        while !self.check(closing) {
+            let field_start = self.peek_token().span;
+            let field_name = match self.peek_token().kind {
+                TokenKind::Identifier(field_name) => {
+                    self.advance();
+                    field_name
+                },
+                _ => return Err(self.error("field name")),
+            };
+
+            if !self.match_token(&TokenKind::Colon) {
+                return Err(self.error("':' after field name"));
+            }
+            let type_name = self.parse_type_name()?;
+
+            fields.push(Parameter {
+                name: field_name,
+                type_name,
+                span: field_start.to(self.previous_span()),
+            });
+
+            if !self.match_token(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        Ok(fields)
+    }
+
+    fn parse_type_declaration(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'type' token
+        let start = self.advance().span;
+
+        // Get type name
+        let name = match self.peek_token().kind {
+            TokenKind::Identifier(name) => {
+                self.advance();
+                name
+            },
+            _ => return Err(self.error("type name after 'type' keyword")),
+        };
+
+        let open = self.peek_token().span;
+        if !self.match_token(&TokenKind::LeftBrace) {
+            return Err(self.error("'{' after type name"));
+        }
+
+        // Fields are written like parameters, separated by commas
+        let fields = self.parse_fields(&TokenKind::RightBrace)?;
+
+        if !self.match_token(&TokenKind::RightBrace) {
+            return Err(self
+                .error("'}' after fields")
+                .with_label(open, "unclosed '{' opened here"));
+        }
+
+        Ok(Statement::new(
+            StatementKind::TypeDeclaration { name, fields },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    fn parse_return_statement(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'return' token
+        let start = self.advance().span;
+
+        // Statements aren't terminated, so a value must start on the same
+        // line as 'return'. Anything else is a bare 'return'.
+        let next = self.peek_token();
+        if next.span.line != start.line || matches!(next.kind, TokenKind::RightBrace | TokenKind::Eof) {
+            return Ok(Statement::new(StatementKind::Return(None), start));
+        }
+
+        let value = self.parse_expression()?;
+        let span = start.to(value.span);
+
+        Ok(Statement::new(StatementKind::Return(Some(value)), span))
+    }
+
+    fn parse_if_statement(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'if' token
+        let start = self.advance().span;
+
         // Parse condition
         let condition = self.parse_expression()?;
-        
+
+        // Parse if body
+        let body = self.parse_block("if condition", "if body")?;
+
+        // Parse an optional 'else' or 'else if' branch
+        let else_body = if self.match_token(&TokenKind::Else) {
+            if self.check(&TokenKind::If) {
+                Some(vec![self.parse_if_statement()?])
+            } else {
+                Some(self.parse_block("'else'", "else body")?)
+            }
+        } else {
+            None
+        };
+
+        Ok(Statement::new(
+            StatementKind::IfStatement { condition, body, else_body },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    // Parses a brace-delimited list of statements. `opened_after` and
+    // `body_name` describe the surrounding construct in error messages.
+    fn parse_block(&mut self, opened_after: &str, body_name: &str) -> Result<Vec<Statement>, Diagnostic<ParseError>> {
         // Consume opening brace
-        if !self.match_token(&Token::LeftBrace) {
-            return Err("Expected '{' after if condition".to_string());
+        let open = self.peek_token().span;
+        if !self.match_token(&TokenKind::LeftBrace) {
+            return Err(self.error(&format!("'{{' after {}", opened_after)));
         }
-        
-        // Parse if body
+
         let mut body = Vec::new();
-        while !self.check(&Token::RightBrace) && !self.is_at_end() {
-            let statement = self.parse_statement()?;
-            body.push(statement);
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            let start = self.current_position;
+            match self.parse_statement() {
+                Ok(statement) => body.push(statement),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize(start);
+                }
+            }
         }
-        
+
         // Consume closing brace
-        if !self.match_token(&Token::RightBrace) {
-            return Err("Expected '}' after if body".to_string());
-        }
-        
-        Ok(Statement::IfStatement { condition, body })
-    }
-
-    fn parse_expression(&mut self) -> Result<Expression, String> {
-        let expr = self.parse_primary_expression()?;
-        
-        // Check for binary operations like 'is' and 'is not'
-        if self.check(&Token::Equals) {
-            self.advance(); // Consume the 'is' token
-            let right = self.parse_primary_expression()?;
-            return Ok(Expression::BinaryOperation {
-                left: Box::new(expr),
-                operator: "is".to_string(),
-                right: Box::new(right),
-            });
-        } else if self.check(&Token::NotEquals) {
-            self.advance(); // Consume the 'is not' token
-            let right = self.parse_primary_expression()?;
-            return Ok(Expression::BinaryOperation {
-                left: Box::new(expr),
-                operator: "is not".to_string(),
-                right: Box::new(right),
-            });
+        if !self.match_token(&TokenKind::RightBrace) {
+            return Err(self
+                .error(&format!("'}}' after {}", body_name))
+                .with_label(open, "unclosed '{' opened here"));
         }
-        
+
+        Ok(body)
+    }
+
+    fn parse_while_statement(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'while' token
+        let start = self.advance().span;
+
+        // Parse condition
+        let condition = self.parse_expression()?;
+
+        // Parse loop body
+        let body = self.parse_block("while condition", "while body")?;
+
+        Ok(Statement::new(
+            StatementKind::WhileStatement { condition, body },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    fn parse_for_statement(&mut self) -> Result<Statement, Diagnostic<ParseError>> {
+        // Consume 'for' token
+        let start = self.advance().span;
+
+        // Get loop variable name
+        let variable = match self.peek_token().kind {
+            TokenKind::Identifier(name) => {
+                self.advance();
+                name
+            },
+            _ => return Err(self.error("loop variable after 'for'")),
+        };
+
+        if !self.match_token(&TokenKind::In) {
+            return Err(self.error("'in' after loop variable"));
+        }
+
+        let iterable = self.parse_expression()?;
+        let end = if self.match_token(&TokenKind::To) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
+        // Parse loop body
+        let body = self.parse_block("for loop header", "for body")?;
+
+        Ok(Statement::new(
+            StatementKind::ForStatement { variable, iterable, end, body },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, Diagnostic<ParseError>> {
+        self.parse_binary_expression(0)
+    }
+
+    // Precedence climbing: parses an operand, then keeps folding in binary
+    // operators that bind at least as tightly as `min_precedence`. Parsing
+    // the right operand one level higher makes every operator left-associative.
+    fn parse_binary_expression(&mut self, min_precedence: u8) -> Result<Expression, Diagnostic<ParseError>> {
+        let mut left = self.parse_unary_expression()?;
+
+        while let Some((operator, precedence)) = Self::binary_operator(&self.peek_token().kind) {
+            // Statements aren't terminated, so an operator at the start of a
+            // line begins the next statement rather than continuing this one
+            if precedence < min_precedence || self.peek_token().starts_line {
+                break;
+            }
+            self.advance(); // Consume the operator
+
+            let right = self.parse_binary_expression(precedence + 1)?;
+            let span = left.span.to(right.span);
+            left = Expression::new(
+                ExpressionKind::BinaryOperation {
+                    left: Box::new(left),
+                    operator: operator.to_string(),
+                    right: Box::new(right),
+                },
+                span,
+            );
+        }
+
+        Ok(left)
+    }
+
+    // The operator text and precedence of a token that can join two operands
+    fn binary_operator(kind: &TokenKind) -> Option<(&'static str, u8)> {
+        match kind {
+            TokenKind::Or => Some(("or", 1)),
+            TokenKind::And => Some(("and", 2)),
+            TokenKind::Equals => Some(("is", 4)),
+            TokenKind::NotEquals => Some(("is not", 4)),
+            TokenKind::Less => Some(("<", 5)),
+            TokenKind::LessEqual => Some(("<=", 5)),
+            TokenKind::Greater => Some((">", 5)),
+            TokenKind::GreaterEqual => Some((">=", 5)),
+            TokenKind::Plus => Some(("+", 6)),
+            TokenKind::Minus => Some(("-", 6)),
+            TokenKind::Star => Some(("*", 7)),
+            TokenKind::Slash => Some(("/", 7)),
+            TokenKind::Percent => Some(("%", 7)),
+            _ => None,
+        }
+    }
+
+    fn parse_unary_expression(&mut self) -> Result<Expression, Diagnostic<ParseError>> {
+        // 'not' binds looser than comparisons, so `not a is b` reads as
+        // `not (a is b)` while `not a and b` still reads as `(not a) and b`
+        if self.check(&TokenKind::Not) {
+            let start = self.advance().span;
+            let operand = self.parse_binary_expression(3)?;
+            let span = start.to(operand.span);
+
+            return Ok(Expression::new(
+                ExpressionKind::UnaryOperation {
+                    operator: "not".to_string(),
+                    operand: Box::new(operand),
+                },
+                span,
+            ));
+        }
+
+        if self.check(&TokenKind::Minus) {
+            let start = self.advance().span;
+            let operand = self.parse_unary_expression()?;
+            let span = start.to(operand.span);
+
+            return Ok(Expression::new(
+                ExpressionKind::UnaryOperation {
+                    operator: "-".to_string(),
+                    operand: Box::new(operand),
+                },
+                span,
+            ));
+        }
+
+        self.parse_postfix_expression()
+    }
+
+    // A primary expression followed by any number of `[index]` lookups and
+    // `.field` accesses
+    fn parse_postfix_expression(&mut self) -> Result<Expression, Diagnostic<ParseError>> {
+        let mut expr = self.parse_primary_expression()?;
+
+        loop {
+            if self.match_token(&TokenKind::Dot) {
+                let field = match self.peek_token().kind {
+                    TokenKind::Identifier(field) => {
+                        self.advance();
+                        field
+                    },
+                    _ => return Err(self.error("field name after '.'")),
+                };
+
+                let span = expr.span.to(self.previous_span());
+                expr = Expression::new(
+                    ExpressionKind::FieldAccess {
+                        target: Box::new(expr),
+                        field,
+                    },
+                    span,
+                );
+                continue;
+            }
+
+            if !self.check(&TokenKind::LeftBracket) {
+                break;
+            }
+
+            let open = self.advance().span;
+            let index = self.parse_expression()?;
+            if !self.match_token(&TokenKind::RightBracket) {
+                return Err(self
+                    .error("']' after index")
+                    .with_label(open, "unclosed '[' opened here"));
+            }
+
+            let span = expr.span.to(self.previous_span());
+            expr = Expression::new(
+                ExpressionKind::Index {
+                    target: Box::new(expr),
+                    index: Box::new(index),
+                },
+                span,
+            );
+        }
+
         Ok(expr)
     }
-    
-    fn parse_primary_expression(&mut self) -> Result<Expression, String> {
-        match self.peek_token() {
-            Token::StringLiteral(s) => {
+
+    fn parse_primary_expression(&mut self) -> Result<Expression, Diagnostic<ParseError>> {
+        let token = self.peek_token();
+        let start = token.span;
+
+        match token.kind {
+            TokenKind::Error(e) => {
+                self.advance();
+                self.errors.push(Self::lex_error(e, start));
+                Ok(Expression::new(ExpressionKind::Error, start))
+            },
+            TokenKind::StringLiteral(s) => {
                 self.advance();
-                Ok(Expression::StringLiteral(s))
+                Ok(Expression::new(ExpressionKind::StringLiteral(s), start))
             },
-            Token::IntegerLiteral(i) => {
+            TokenKind::IntegerLiteral(i) => {
                 self.advance();
-                Ok(Expression::IntegerLiteral(i))
+                Ok(Expression::new(ExpressionKind::IntegerLiteral(i), start))
             },
-            Token::Identifier(name) => {
+            TokenKind::Identifier(name)
+                if self.peek_token_at(1).kind == TokenKind::LeftBracket
+                    && matches!(self.peek_token_at(2).kind, TokenKind::Identifier(_))
+                    && self.peek_token_at(3).kind == TokenKind::Colon =>
+            {
+                // `Name[field: ...]` builds a record, where `name[index]` would
+                // only ever have an expression inside the brackets
+                self.parse_record_literal(name)
+            },
+            TokenKind::Identifier(name) => {
                 self.advance();
                 // Check if it's a function call
-                if self.check(&Token::LeftParen) {
+                if self.check(&TokenKind::LeftParen) {
                     self.advance();  // Consume '('
-                    let arguments = self.parse_arguments()?;
-                    if !self.match_token(&Token::RightParen) {
-                        return Err("Expected ')' after function arguments".to_string());
+                    let arguments = self.parse_arguments(&TokenKind::RightParen, "arguments")?;
+                    if !self.match_token(&TokenKind::RightParen) {
+                        return Err(self.error("')' after function arguments"));
                     }
-                    Ok(Expression::FunctionCall { name, arguments })
+                    Ok(Expression::new(
+                        ExpressionKind::FunctionCall { name, arguments },
+                        start.to(self.previous_span()),
+                    ))
                 } else {
-                    Ok(Expression::Identifier(name))
+                    Ok(Expression::new(ExpressionKind::Identifier(name), start))
                 }
             },
-            Token::TypeString | Token::TypeInteger => {
-                let type_name = match self.consume_token() {
-                    Token::TypeString => "String".to_string(),
-                    Token::TypeInteger => "Integer".to_string(),
-                    _ => unreachable!(),
-                };
-                
-                // We expect a left bracket after the type name
-                if !self.match_token(&Token::LeftBracket) {
-                    return Err("Expected '[' after type name".to_string());
-                }
-                
-                // Parse the value inside the brackets
-                let value = self.parse_expression()?;
-                
-                // We expect a right bracket to close
-                if !self.match_token(&Token::RightBracket) {
-                    return Err("Expected ']' after type value".to_string());
+            TokenKind::LeftParen => {
+                self.advance();
+
+                // Parentheses only group, so the inner expression is kept as is
+                let mut expr = self.parse_expression()?;
+                if !self.match_token(&TokenKind::RightParen) {
+                    return Err(self
+                        .error("')' after expression")
+                        .with_label(start, "unclosed '(' opened here"));
                 }
-                
-                Ok(Expression::TypedValue {
-                    type_name,
-                    value: Box::new(value),
-                })
+                expr.span = start.to(self.previous_span());
+                Ok(expr)
+            },
+            TokenKind::BooleanLiteral(b) => {
+                self.advance();
+                Ok(Expression::new(ExpressionKind::BooleanLiteral(b), start))
             },
-            Token::TypeTrue => {
+            TokenKind::TypeList => {
                 self.advance();
-                Ok(Expression::Identifier("True".to_string()))
+
+                let open = self.peek_token().span;
+                if !self.match_token(&TokenKind::LeftBracket) {
+                    return Err(self.error("'[' after 'List'"));
+                }
+                let elements = self.parse_arguments(&TokenKind::RightBracket, "list elements")?;
+                if !self.match_token(&TokenKind::RightBracket) {
+                    return Err(self
+                        .error("']' after list elements")
+                        .with_label(open, "unclosed '[' opened here"));
+                }
+
+                Ok(Expression::new(
+                    ExpressionKind::ListLiteral(elements),
+                    start.to(self.previous_span()),
+                ))
             },
-            Token::TypeFalse => {
+            TokenKind::TypeMap => {
                 self.advance();
-                Ok(Expression::Identifier("False".to_string()))
+
+                let open = self.peek_token().span;
+                if !self.match_token(&TokenKind::LeftBracket) {
+                    return Err(self.error("'[' after 'Map'"));
+                }
+
+                let mut entries = Vec::new();
+                while !self.check(&TokenKind::RightBracket) {
+                    let key = self.parse_expression()?;
+                    if !self.match_token(&TokenKind::Colon) {
+                        return Err(self.error("':' between map key and value"));
+                    }
+                    let value = self.parse_expression()?;
+                    entries.push((key, value));
+
+                    if !self.match_token(&TokenKind::Comma) {
+                        break;
+                    }
+                }
+
+                if !self.match_token(&TokenKind::RightBracket) {
+                    return Err(self
+                        .error("']' after map entries")
+                        .with_label(open, "unclosed '[' opened here"));
+                }
+
+                Ok(Expression::new(
+                    ExpressionKind::MapLiteral(entries),
+                    start.to(self.previous_span()),
+                ))
+            },
+            kind => {
+                let diagnostic = Diagnostic::new(ParseError::UnexpectedToken(kind.clone()), start);
+                if token.starts_line && Self::binary_operator(&kind).is_some() {
+                    return Err(diagnostic.with_note(
+                        "statements end with their line, so move the operator to the end of the previous line",
+                    ));
+                }
+                Err(diagnostic)
             },
-            _ => Err(format!("Unexpected token: {:?}", self.peek_token())),
         }
     }
 
-    fn parse_arguments(&mut self) -> Result<Vec<Expression>, String> {
+    fn parse_record_literal(&mut self, type_name: String) -> Result<Expression, Diagnostic<ParseError>> {
+        let start = self.advance().span;
+
+        // Consume '[', which parse_primary_expression has already seen
+        let open = self.advance().span;
+
+        let mut fields = Vec::new();
+        while !self.check(&TokenKind::RightBracket) {
+            let field_start = self.peek_token().span;
+            let name = match self.peek_token().kind {
+                TokenKind::Identifier(name) => {
+                    self.advance();
+                    name
+                },
+                _ => return Err(self.error("field name")),
+            };
+
+            if !self.match_token(&TokenKind::Colon) {
+                return Err(self.error("':' after field name"));
+            }
+            let value = self.parse_expression()?;
+
+            fields.push(FieldValue {
+                name,
+                span: field_start.to(value.span),
+                value,
+            });
+
+            if !self.match_token(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        if !self.match_token(&TokenKind::RightBracket) {
+            return Err(self
+                .error("']' after record fields")
+                .with_label(open, "unclosed '[' opened here"));
+        }
+
+        Ok(Expression::new(
+            ExpressionKind::RecordLiteral { type_name, fields },
+            start.to(self.previous_span()),
+        ))
+    }
+
+    // Parses comma separated expressions up to, but not including, `closing`
+    fn parse_arguments(&mut self, closing: &TokenKind, what: &str) -> Result<Vec<Expression>, Diagnostic<ParseError>> {
         let mut arguments = Vec::new();
-        
-        // If next token is ')', we have no arguments
-        if self.check(&Token::RightParen) {
+
+        // If next token is the closing one, we have no arguments
+        if self.check(closing) {
             return Ok(arguments);
         }
-        
+
         loop {
             let argument = self.parse_expression()?;
             arguments.push(argument);
-            
-            // If next token is ')', we're done
-            if self.check(&Token::RightParen) {
+
+            // If next token is the closing one, we're done
+            if self.check(closing) {
                 break;
             }
-            
+
             // Otherwise, expect a comma
-            if !self.check(&Token::Comma) {
-                return Err("Expected ',' between arguments".to_string());
+            if !self.check(&TokenKind::Comma) {
+                return Err(self.error(&format!("',' between {}", what)));
             }
-            
+
             // Consume comma
             self.advance();
         }
-        
+
         Ok(arguments)
     }
 
+    // Reports that `expected` was missing at the token we are currently looking at
+    fn error(&self, expected: &str) -> Diagnostic<ParseError> {
+        let token = self.peek_token();
+
+        // A token the lexer couldn't read explains the problem better than
+        // what was expected in its place
+        if let TokenKind::Error(e) = token.kind {
+            return Self::lex_error(e, token.span);
+        }
+
+        Diagnostic::new(
+            ParseError::Expected {
+                expected: expected.to_string(),
+                found: token.kind,
+            },
+            token.span,
+        )
+    }
+
     fn peek_token(&self) -> Token {
-        if self.current_position >= self.tokens.len() {
-            Token::EOF
-        } else {
-            self.tokens[self.current_position].clone()
+        self.peek_token_at(0)
+    }
+
+    // Looks `offset` tokens past the current one
+    fn peek_token_at(&self, offset: usize) -> Token {
+        match self.tokens.get(self.current_position + offset) {
+            Some(token) => token.clone(),
+            // The lexer always ends with Eof, so reuse its position
+            None => Token::new(
+                TokenKind::Eof,
+                self.tokens.last().map(|token| token.span).unwrap_or_default(),
+            ),
         }
     }
 
+    fn previous_span(&self) -> Span {
+        self.current_position
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i).or(self.tokens.last()))
+            .map(|token| token.span)
+            .unwrap_or_default()
+    }
+
     fn advance(&mut self) -> Token {
         let token = self.peek_token();
         self.current_position += 1;
         token
     }
 
-    fn consume_token(&mut self) -> Token {
-        self.advance()
-    }
-
     fn is_at_end(&self) -> bool {
-        self.peek_token() == Token::EOF
+        self.peek_token().kind == TokenKind::Eof
     }
 
-    fn check(&self, token_type: &Token) -> bool {
-        &self.peek_token() == token_type
+    fn check(&self, token_type: &TokenKind) -> bool {
+        &self.peek_token().kind == token_type
     }
 
-    fn match_token(&mut self, token_type: &Token) -> bool {
+    fn match_token(&mut self, token_type: &TokenKind) -> bool {
         if self.check(token_type) {
             self.advance();
             true
@@ -362,4 +1351,4 @@ impl Parser {
             false
         }
     }
-}
\ No newline at end of file
+}
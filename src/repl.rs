@@ -0,0 +1,175 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crate::diagnostic::render_all;
+use crate::interpreter::{Interpreter, Value};
+use crate::lexer::{Lexer, TokenKind};
+use crate::parser::{Parser, StatementKind};
+use crate::typechecker::TypeChecker;
+
+const HISTORY_FILE: &str = ".noam_history";
+const HISTORY_LIMIT: usize = 1000;
+const PROMPT: &str = "noam> ";
+const CONTINUATION_PROMPT: &str = "  ... ";
+
+// Interactive session that keeps one interpreter and one type checker
+// alive, so definitions from earlier inputs stay visible to later ones
+pub struct Repl {
+    interpreter: Interpreter,
+    type_checker: TypeChecker,
+    // Every entry so far, so diagnostics can point back into earlier ones
+    source: String,
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let history_path = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+
+        // A missing or unreadable history file just means we start fresh
+        let history = history_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| contents.lines().map(|line| line.to_string()).collect())
+            .unwrap_or_default();
+
+        Repl {
+            interpreter: Interpreter::new(),
+            type_checker: TypeChecker::new(),
+            source: String::new(),
+            history,
+            history_path,
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+
+        println!("noam repl, type :help for commands");
+
+        while let Some(entry) = self.read_entry(&mut input)? {
+            let trimmed = entry.trim();
+
+            match trimmed {
+                "" => continue,
+                ":quit" | ":exit" => break,
+                ":help" => {
+                    println!(":history  show previous inputs");
+                    println!(":quit     leave the repl");
+                    continue;
+                },
+                ":history" => {
+                    for (i, line) in self.history.iter().enumerate() {
+                        println!("{:>4}  {}", i + 1, line);
+                    }
+                    continue;
+                },
+                _ => {}
+            }
+
+            self.history.extend(entry.lines().map(|line| line.to_string()));
+            self.evaluate(&entry);
+        }
+
+        self.save_history()
+    }
+
+    // Reads one entry, continuing onto further lines while a '{' is still open.
+    // Returns None once stdin is exhausted.
+    fn read_entry(&self, input: &mut impl BufRead) -> io::Result<Option<String>> {
+        let mut entry = String::new();
+        let mut prompt = PROMPT;
+
+        loop {
+            print!("{}", prompt);
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                println!();
+                return Ok(if entry.is_empty() { None } else { Some(entry) });
+            }
+
+            entry.push_str(&line);
+
+            if open_braces(&entry) <= 0 {
+                return Ok(Some(entry));
+            }
+
+            prompt = CONTINUATION_PROMPT;
+        }
+    }
+
+    fn evaluate(&mut self, entry: &str) {
+        // Each entry is lexed in place at the end of the session's source, so
+        // spans from earlier entries still point at the right text
+        let start = self.source.len();
+        self.source.push_str(entry);
+        if !entry.ends_with('\n') {
+            self.source.push('\n');
+        }
+        let source = self.source.as_str();
+
+        let tokens = Lexer::new(source).starting_at(start).tokenize();
+
+        let (program, errors) = Parser::new(tokens).parse();
+        if !errors.is_empty() {
+            eprintln!("{}", render_all(&errors, "<repl>", source));
+            return;
+        }
+
+        // The checker records an entry's declarations as it goes, so it is
+        // rolled back whenever the interpreter doesn't get to run them all
+        let snapshot = self.type_checker.clone();
+        if let Err(errors) = self.type_checker.check_program(&program) {
+            self.type_checker = snapshot;
+            eprintln!("{}", render_all(&errors, "<repl>", source));
+            return;
+        }
+
+        // Only bare expressions echo their value, not declarations or ifs
+        let echo = matches!(program.statements.last(), Some(statement) if matches!(statement.kind, StatementKind::Expression(_)));
+
+        match self.interpreter.interpret(program) {
+            Ok(Value::Null) => {},
+            Ok(value) => {
+                if echo {
+                    println!("{}", value);
+                }
+            },
+            Err(e) => {
+                self.type_checker = snapshot;
+                eprintln!("{}", e.render("<repl>", source));
+            },
+        }
+    }
+
+    fn save_history(&self) -> io::Result<()> {
+        match &self.history_path {
+            Some(path) => {
+                let start = self.history.len().saturating_sub(HISTORY_LIMIT);
+                let mut contents = self.history[start..].join("\n");
+                contents.push('\n');
+                fs::write(path, contents)
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+// Counts how many '{' are still waiting for their '}'
+fn open_braces(source: &str) -> i32 {
+    Lexer::new(source)
+        .tokenize()
+        .iter()
+        .map(|token| match token.kind {
+            TokenKind::LeftBrace => 1,
+            TokenKind::RightBrace => -1,
+            _ => 0,
+        })
+        .sum()
+}
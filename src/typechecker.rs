@@ -1,4 +1,9 @@
-use crate::parser::{Expression, Program, Statement};
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::interpreter::BUILTINS;
+use crate::parser::{
+    Expression, ExpressionKind, Parameter, Program, Statement, StatementKind, TypeName, TypeNameKind,
+};
+use crate::span::Span;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -7,10 +12,19 @@ pub enum Type {
     Integer,
     Boolean,
     Void,
+    List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    // A user-defined record, named by its 'type' declaration
+    Record(String),
+    // A user-defined sum type, named by its 'enum' declaration
+    Enum(String),
     Function {
         parameters: Vec<Type>,
         return_type: Box<Type>,
     },
+    // print and the list and map built-ins, whose calls are checked by
+    // `check_builtin_call` since their types depend on the arguments
+    Builtin(String),
     Unknown,
 }
 
@@ -21,6 +35,9 @@ impl std::fmt::Display for Type {
             Type::Integer => write!(f, "Integer"),
             Type::Boolean => write!(f, "Boolean"),
             Type::Void => write!(f, "Void"),
+            Type::List(element_type) => write!(f, "List[{}]", element_type),
+            Type::Map(key_type, value_type) => write!(f, "Map[{}, {}]", key_type, value_type),
+            Type::Record(name) | Type::Enum(name) => write!(f, "{}", name),
             Type::Function { parameters, return_type } => {
                 write!(f, "fn(")?;
                 for (i, param) in parameters.iter().enumerate() {
@@ -31,6 +48,7 @@ impl std::fmt::Display for Type {
                 }
                 write!(f, ") -> {}", return_type)
             },
+            Type::Builtin(name) => write!(f, "built-in {}", name),
             Type::Unknown => write!(f, "Unknown"),
         }
     }
@@ -39,6 +57,8 @@ impl std::fmt::Display for Type {
 #[derive(Clone)]
 pub struct TypeEnvironment {
     types: HashMap<String, Type>,
+    // Where user-defined names were declared, for pointing diagnostics at them
+    spans: HashMap<String, Span>,
     parent: Option<Box<TypeEnvironment>>,
 }
 
@@ -46,14 +66,16 @@ impl TypeEnvironment {
     pub fn new() -> Self {
         let mut env = TypeEnvironment {
             types: HashMap::new(),
+            spans: HashMap::new(),
             parent: None,
         };
         
-        // Add built-in functions
-        env.define("print".to_string(), Type::Function {
-            parameters: vec![Type::Unknown],  // print can take any type
-            return_type: Box::new(Type::Void),
-        });
+        // Add built-in functions. A user function with the same name
+        // shadows them like any other variable.
+        env.define("print".to_string(), Type::Builtin("print".to_string()));
+        for (name, _) in BUILTINS {
+            env.define(name.to_string(), Type::Builtin(name.to_string()));
+        }
         
         // Add the dummy 'function' function
         env.define("function".to_string(), Type::Function {
@@ -67,13 +89,31 @@ impl TypeEnvironment {
     pub fn extend(parent: TypeEnvironment) -> Self {
         TypeEnvironment {
             types: HashMap::new(),
+            spans: HashMap::new(),
             parent: Some(Box::new(parent)),
         }
     }
     
     pub fn define(&mut self, name: String, ty: Type) {
+        self.spans.remove(&name);
+        self.types.insert(name, ty);
+    }
+
+    pub fn define_at(&mut self, name: String, ty: Type, span: Span) {
+        self.spans.insert(name.clone(), span);
         self.types.insert(name, ty);
     }
+
+    // Finds the span of the declaration `get` would resolve the name to
+    pub fn get_span(&self, name: &str) -> Option<Span> {
+        if self.types.contains_key(name) {
+            self.spans.get(name).copied()
+        } else if let Some(parent) = &self.parent {
+            parent.get_span(name)
+        } else {
+            None
+        }
+    }
     
     pub fn get(&self, name: &str) -> Option<Type> {
         match self.types.get(name) {
@@ -89,45 +129,293 @@ impl TypeEnvironment {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    NotAFunction(String),
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+    },
+    NonBooleanCondition(Type),
+    UnknownOperator(String),
+    AssignToFunction(String),
+    ReturnOutsideFunction,
+    OutsideLoop(String),
+    InvalidOperand {
+        operator: String,
+        expected: String,
+        found: Type,
+    },
+    NotIndexable(Type),
+    InvalidMapKey(Type),
+    NotIterable(Type),
+    UnknownType(String),
+    UnknownField {
+        ty: Type,
+        field: String,
+    },
+    MissingField {
+        record: String,
+        field: String,
+    },
+    DuplicateField {
+        record: String,
+        field: String,
+    },
+    NotMatchable(Type),
+    NonExhaustiveMatch {
+        ty: Type,
+        missing: Vec<String>,
+    },
+    UnknownVariant {
+        ty: Type,
+        variant: String,
+    },
+    DuplicateVariant {
+        ty: Type,
+        variant: String,
+    },
+    VariantAlreadyDeclared {
+        ty: Type,
+        variant: String,
+    },
+    NameAlreadyDeclared(String),
+    InvalidArgument {
+        name: String,
+        expected: String,
+        found: Type,
+    },
+    MissingReturn {
+        name: String,
+        expected: Type,
+    },
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::UndefinedVariable(name) => write!(f, "Undefined variable '{}'", name),
+            TypeError::UndefinedFunction(name) => write!(f, "Undefined function '{}'", name),
+            TypeError::NotAFunction(name) => write!(f, "'{}' is not a function", name),
+            TypeError::ArityMismatch { name, expected, found } => write!(
+                f,
+                "Function '{}' expects {} arguments, got {}",
+                name, expected, found
+            ),
+            TypeError::TypeMismatch { expected, found } => {
+                write!(f, "Type mismatch: expected {}, got {}", expected, found)
+            },
+            TypeError::NonBooleanCondition(found) => {
+                write!(f, "Condition must be a boolean, got {}", found)
+            },
+            TypeError::UnknownOperator(operator) => write!(f, "Unknown operator: {}", operator),
+            TypeError::AssignToFunction(name) => write!(f, "Cannot assign to function '{}'", name),
+            TypeError::ReturnOutsideFunction => write!(f, "'return' outside of a function"),
+            TypeError::OutsideLoop(keyword) => write!(f, "'{}' outside of a loop", keyword),
+            TypeError::InvalidOperand { operator, expected, found } => {
+                write!(f, "Operator '{}' expects {} operands, got {}", operator, expected, found)
+            },
+            TypeError::NotIndexable(found) => write!(f, "Cannot index into a value of type {}", found),
+            TypeError::NotIterable(found) => write!(f, "Cannot loop over a value of type {}", found),
+            TypeError::UnknownType(name) => write!(f, "Unknown type '{}'", name),
+            TypeError::UnknownField { ty, field } => write!(f, "No field '{}' on type {}", field, ty),
+            TypeError::MissingField { record, field } => {
+                write!(f, "Missing field '{}' in '{}'", field, record)
+            },
+            TypeError::DuplicateField { record, field } => {
+                write!(f, "Field '{}' appears more than once in '{}'", field, record)
+            },
+            TypeError::NotMatchable(found) => write!(f, "Cannot match on a value of type {}", found),
+            TypeError::NonExhaustiveMatch { ty, missing } => write!(
+                f,
+                "Match on {} doesn't cover {}",
+                ty,
+                missing
+                    .iter()
+                    .map(|variant| format!("'{}'", variant))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TypeError::UnknownVariant { ty, variant } => {
+                write!(f, "'{}' is not a variant of {}", variant, ty)
+            },
+            TypeError::DuplicateVariant { ty, variant } => {
+                write!(f, "Variant '{}' appears more than once in {}", variant, ty)
+            },
+            TypeError::VariantAlreadyDeclared { ty, variant } => {
+                write!(f, "Variant '{}' is already declared by {}", variant, ty)
+            },
+            TypeError::NameAlreadyDeclared(name) => {
+                write!(f, "'{}' is already declared as a type, enum or variant", name)
+            },
+            TypeError::InvalidMapKey(found) => {
+                write!(f, "Map keys must be String or Integer, got {}", found)
+            },
+            TypeError::InvalidArgument { name, expected, found } => {
+                write!(f, "'{}' expects {}, got {}", name, expected, found)
+            },
+            TypeError::MissingReturn { name, expected } => write!(
+                f,
+                "Function '{}' must return {} on every path",
+                name, expected
+            ),
+        }
+    }
+}
+
+impl ErrorCode for TypeError {
+    fn code(&self) -> &'static str {
+        match self {
+            TypeError::UndefinedVariable(_) => "E0301",
+            TypeError::UndefinedFunction(_) => "E0302",
+            TypeError::NotAFunction(_) => "E0303",
+            TypeError::ArityMismatch { .. } => "E0304",
+            TypeError::TypeMismatch { .. } => "E0305",
+            TypeError::NonBooleanCondition(_) => "E0306",
+            TypeError::UnknownOperator(_) => "E0307",
+            TypeError::AssignToFunction(_) => "E0308",
+            TypeError::ReturnOutsideFunction => "E0309",
+            TypeError::MissingReturn { .. } => "E0310",
+            TypeError::OutsideLoop(_) => "E0311",
+            TypeError::InvalidOperand { .. } => "E0312",
+            TypeError::NotIndexable(_) => "E0313",
+            TypeError::InvalidArgument { .. } => "E0314",
+            TypeError::InvalidMapKey(_) => "E0315",
+            TypeError::NotIterable(_) => "E0316",
+            TypeError::UnknownType(_) => "E0317",
+            TypeError::UnknownField { .. } => "E0318",
+            TypeError::MissingField { .. } => "E0319",
+            TypeError::DuplicateField { .. } => "E0320",
+            TypeError::NotMatchable(_) => "E0321",
+            TypeError::NonExhaustiveMatch { .. } => "E0322",
+            TypeError::UnknownVariant { .. } => "E0323",
+            TypeError::DuplicateVariant { .. } => "E0324",
+            TypeError::VariantAlreadyDeclared { .. } => "E0325",
+            TypeError::NameAlreadyDeclared(_) => "E0326",
+        }
+    }
+}
+
+// The fields of a declared record type, in declaration order
+#[derive(Clone)]
+struct RecordType {
+    fields: Vec<(String, Type)>,
+    span: Span,
+}
+
+// The variant names of a declared enum, in declaration order
+#[derive(Clone)]
+struct EnumType {
+    variants: Vec<String>,
+    span: Span,
+}
+
+#[derive(Clone)]
 pub struct TypeChecker {
     environment: TypeEnvironment,
+    // Record types declared so far, which type names can refer to
+    records: HashMap<String, RecordType>,
+    enums: HashMap<String, EnumType>,
+    // The enum each variant belongs to, and the fields of its payload
+    variants: HashMap<String, (String, RecordType)>,
+    errors: Vec<Diagnostic<TypeError>>,
+    // Declared return type of the function whose body is being checked
+    return_type: Option<Type>,
+    // How many loops enclose the statement being checked, within its function
+    loop_depth: usize,
 }
 
 impl TypeChecker {
     pub fn new() -> Self {
         TypeChecker {
             environment: TypeEnvironment::new(),
+            records: HashMap::new(),
+            enums: HashMap::new(),
+            variants: HashMap::new(),
+            errors: Vec::new(),
+            return_type: None,
+            loop_depth: 0,
         }
     }
     
-    pub fn check_program(&mut self, program: &Program) -> Result<(), String> {
-        for statement in &program.statements {
-            self.check_statement(statement)?;
+    // Checks every statement, reporting all type errors found rather than
+    // stopping at the first one
+    pub fn check_program(&mut self, program: &Program) -> Result<(), Vec<Diagnostic<TypeError>>> {
+        self.check_block(&program.statements);
+
+        let errors = std::mem::take(&mut self.errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Checks each statement in turn, recording errors and moving on. The
+    // block's type is the type of its last statement.
+    fn check_block(&mut self, statements: &[Statement]) -> Type {
+        let mut block_type = Type::Void;
+        for statement in statements {
+            block_type = match self.check_statement(statement) {
+                Ok(ty) => ty,
+                Err(e) => {
+                    self.errors.push(e);
+                    Type::Unknown
+                },
+            };
         }
-        Ok(())
+        block_type
+    }
+    
+    // Checks a block in its own scope, so names it declares don't leak out
+    fn check_scoped_block(&mut self, statements: &[Statement]) -> Type {
+        let current_env = self.environment.clone();
+        let prev_env = std::mem::replace(&mut self.environment, TypeEnvironment::extend(current_env));
+        
+        let block_type = self.check_block(statements);
+        
+        self.environment = prev_env;
+        block_type
     }
     
-    fn check_statement(&mut self, statement: &Statement) -> Result<Type, String> {
-        match statement {
-            Statement::Expression(expr) => self.check_expression(expr),
+    fn check_statement(&mut self, statement: &Statement) -> Result<Type, Diagnostic<TypeError>> {
+        match &statement.kind {
+            StatementKind::Expression(expr) => self.check_expression(expr),
             
-            Statement::FunctionDeclaration { name, parameters, body } => {
+            StatementKind::FunctionDeclaration { name, parameters, return_type, body, .. } => {
                 // Collect parameter types
                 let mut param_types = Vec::new();
                 
                 for param in parameters {
-                    let param_type = self.parse_type_name(&param.type_name);
+                    let param_type = self.resolve_type(&param.type_name);
                     param_types.push(param_type);
                 }
                 
+                // Functions without a declared return type return Void. One
+                // that can't be resolved is already reported, so missing
+                // returns aren't reported on top of it.
+                let errors_before = self.errors.len();
+                let declared_return = match return_type {
+                    Some(type_name) => self.resolve_type(type_name),
+                    None => Type::Void,
+                };
+                let return_type_resolved = self.errors.len() == errors_before;
+                
                 // Create function type
                 let func_type = Type::Function {
                     parameters: param_types.clone(),
-                    return_type: Box::new(Type::Void),  // Default return type
+                    return_type: Box::new(declared_return.clone()),
                 };
                 
                 // Define function in environment before checking body
-                self.environment.define(name.clone(), func_type);
+                self.environment.define_at(name.clone(), func_type, statement.span);
                 
                 // Create a new environment for function body
                 let current_env = self.environment.clone();
@@ -136,72 +424,478 @@ impl TypeChecker {
                 
                 // Add parameters to the new environment
                 for (param, param_type) in parameters.iter().zip(param_types) {
-                    self.environment.define(param.name.clone(), param_type);
+                    self.environment.define_at(param.name.clone(), param_type, param.span);
                 }
                 
-                // Check function body
-                for stmt in body {
-                    self.check_statement(stmt)?;
-                }
+                // Check function body against the declared return type
+                // 'break' and 'continue' can't reach loops outside the function
+                let outer_return = self.return_type.replace(declared_return.clone());
+                let outer_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                self.check_block(body);
+                self.return_type = outer_return;
+                self.loop_depth = outer_loop_depth;
                 
                 // Restore previous environment
                 self.environment = prev_env;
                 
+                if declared_return != Type::Void && return_type_resolved && !Self::always_returns(body) {
+                    return Err(Diagnostic::new(
+                        TypeError::MissingReturn {
+                            name: name.clone(),
+                            expected: declared_return,
+                        },
+                        statement.span,
+                    )
+                    .with_note("add a 'return' at the end of the function body"));
+                }
+                
                 Ok(Type::Void)
             },
             
-            Statement::IfStatement { condition, body } => {
+            StatementKind::IfStatement { condition, body, else_body } => {
                 // Check condition
                 let cond_type = self.check_expression(condition)?;
                 
                 // In a more strict language, we'd require condition to be boolean
                 if cond_type != Type::Boolean && cond_type != Type::Unknown {
-                    return Err(format!(
-                        "If condition must be a boolean, got {}", cond_type
+                    self.errors.push(Diagnostic::new(
+                        TypeError::NonBooleanCondition(cond_type),
+                        condition.span,
                     ));
                 }
                 
-                // Check body
-                for stmt in body {
-                    self.check_statement(stmt)?;
+                // Check both branches. When they agree on a type, that is the
+                // type of the whole if, otherwise it produces nothing.
+                let body_type = self.check_scoped_block(body);
+                let else_type = match else_body {
+                    Some(else_body) => self.check_scoped_block(else_body),
+                    None => return Ok(Type::Void),
+                };
+                
+                if body_type == else_type {
+                    Ok(body_type)
+                } else {
+                    Ok(Type::Void)
+                }
+            },
+            
+            StatementKind::VariableDeclaration { name, type_name, value } => {
+                let value_type = self.check_expression(value)?;
+
+                // An annotation fixes the type, otherwise it is inferred from the value.
+                // A mismatch still declares the name, so later uses aren't reported too.
+                let variable_type = match type_name {
+                    Some(type_name) => {
+                        let declared_type = self.resolve_type(type_name);
+                        if !self.types_compatible(&value_type, &declared_type) {
+                            self.errors.push(Diagnostic::new(
+                                TypeError::TypeMismatch {
+                                    expected: declared_type.clone(),
+                                    found: value_type,
+                                },
+                                value.span,
+                            ));
+                        }
+                        declared_type
+                    },
+                    None => value_type,
+                };
+
+                self.environment.define_at(name.clone(), variable_type, statement.span);
+
+                Ok(Type::Void)
+            },
+
+            StatementKind::Assignment { name, value } => {
+                let variable_type = match self.environment.get(name) {
+                    Some(ty) => ty,
+                    None => {
+                        return Err(Diagnostic::new(
+                            TypeError::UndefinedVariable(name.clone()),
+                            statement.span,
+                        ));
+                    },
+                };
+
+                if let Type::Function { .. } | Type::Builtin(_) = variable_type {
+                    return Err(Diagnostic::new(
+                        TypeError::AssignToFunction(name.clone()),
+                        statement.span,
+                    ));
+                }
+
+                let value_type = self.check_expression(value)?;
+
+                if !self.types_compatible(&value_type, &variable_type) {
+                    let mut diagnostic = Diagnostic::new(
+                        TypeError::TypeMismatch {
+                            expected: variable_type.clone(),
+                            found: value_type,
+                        },
+                        value.span,
+                    );
+                    if let Some(span) = self.environment.get_span(name) {
+                        diagnostic = diagnostic.with_label(
+                            span,
+                            format!("'{}' declared as {} here", name, variable_type),
+                        );
+                    }
+                    return Err(diagnostic);
+                }
+
+                Ok(Type::Void)
+            },
+
+            StatementKind::FieldAssignment { name, fields, value } => {
+                let mut field_type = match self.environment.get(name) {
+                    Some(ty) => ty,
+                    None => {
+                        return Err(Diagnostic::new(
+                            TypeError::UndefinedVariable(name.clone()),
+                            statement.span,
+                        ));
+                    },
+                };
+                for field in fields {
+                    field_type = self.field_type(&field_type, field, statement.span)?;
                 }
                 
+                let value_type = self.check_expression(value)?;
+                self.check_argument(&value_type, &field_type, value)?;
+                
                 Ok(Type::Void)
             },
             
-            Statement::Comment(_) => Ok(Type::Void),
+            StatementKind::TypeDeclaration { name, fields } => {
+                self.check_name_free(name, statement.span)?;
+                
+                // Declare the record first, so its fields can refer to it
+                self.records.insert(name.clone(), RecordType {
+                    fields: Vec::new(),
+                    span: statement.span,
+                });
+                let record = self.record_type(name, fields, statement.span);
+                self.records.insert(name.clone(), record);
+                Ok(Type::Void)
+            },
+            
+            StatementKind::EnumDeclaration { name, variants } => {
+                let enum_type = Type::Enum(name.clone());
+                let mut declared = EnumType {
+                    variants: Vec::new(),
+                    span: statement.span,
+                };
+                
+                self.check_name_free(name, statement.span)?;
+                
+                // Declare the enum first, so payloads can refer to it
+                self.enums.insert(name.clone(), declared.clone());
+                
+                for variant in variants {
+                    if declared.variants.contains(&variant.name) {
+                        self.errors.push(Diagnostic::new(
+                            TypeError::DuplicateVariant {
+                                ty: enum_type.clone(),
+                                variant: variant.name.clone(),
+                            },
+                            variant.span,
+                        ));
+                        continue;
+                    }
+                    
+                    // Variants are written without their enum's name, so each
+                    // name can only belong to one enum
+                    if let Some((owner, _)) = self.variants.get(&variant.name) {
+                        let mut diagnostic = Diagnostic::new(
+                            TypeError::VariantAlreadyDeclared {
+                                ty: Type::Enum(owner.clone()),
+                                variant: variant.name.clone(),
+                            },
+                            variant.span,
+                        );
+                        if let Some(owner_enum) = self.enums.get(owner) {
+                            diagnostic = diagnostic.with_label(owner_enum.span, format!("{} declared here", owner));
+                        }
+                        self.errors.push(diagnostic.with_note("give the variant a name no other enum uses"));
+                        continue;
+                    }
+                    
+                    if let Err(diagnostic) = self.check_name_free(&variant.name, variant.span) {
+                        self.errors.push(diagnostic);
+                        continue;
+                    }
+                    
+                    let payload = self.record_type(&variant.name, &variant.fields, variant.span);
+                    self.variants.insert(variant.name.clone(), (name.clone(), payload));
+                    declared.variants.push(variant.name.clone());
+                }
+                
+                self.enums.insert(name.clone(), declared);
+                Ok(Type::Void)
+            },
+            
+            StatementKind::Match { value, arms } => {
+                let value_type = self.check_expression(value)?;
+                
+                // The enum being matched on, if the checker knows it
+                let declared = match &value_type {
+                    Type::Enum(name) => self.enums.get(name).cloned(),
+                    Type::Unknown => None,
+                    other => {
+                        return Err(Diagnostic::new(TypeError::NotMatchable(other.clone()), value.span));
+                    },
+                };
+                
+                let mut covered: Vec<&String> = Vec::new();
+                let mut has_else = false;
+                let mut arm_types = Vec::new();
+                
+                for arm in arms {
+                    // Bindings of payload fields, with the type of each
+                    let mut bindings = Vec::new();
+                    
+                    match &arm.variant {
+                        None => has_else = true,
+                        Some(variant) => {
+                            let payload = match self.variants.get(variant) {
+                                Some((enum_name, payload)) if value_type == Type::Enum(enum_name.clone()) => {
+                                    Some(payload.clone())
+                                },
+                                _ if declared.is_none() => None,
+                                _ => {
+                                    self.errors.push(Diagnostic::new(
+                                        TypeError::UnknownVariant {
+                                            ty: value_type.clone(),
+                                            variant: variant.clone(),
+                                        },
+                                        arm.span,
+                                    ));
+                                    None
+                                },
+                            };
+                            
+                            if covered.contains(&variant) {
+                                self.errors.push(Diagnostic::new(
+                                    TypeError::DuplicateVariant {
+                                        ty: value_type.clone(),
+                                        variant: variant.clone(),
+                                    },
+                                    arm.span,
+                                )
+                                .with_note("an earlier arm already matches this variant"));
+                            }
+                            covered.push(variant);
+                            
+                            for (field, binding) in &arm.bindings {
+                                let field_type = match &payload {
+                                    Some(payload) => match payload.fields.iter().find(|(name, _)| name == field) {
+                                        Some((_, field_type)) => field_type.clone(),
+                                        None => {
+                                            self.errors.push(
+                                                Diagnostic::new(
+                                                    TypeError::UnknownField {
+                                                        ty: value_type.clone(),
+                                                        field: field.clone(),
+                                                    },
+                                                    arm.span,
+                                                )
+                                                .with_label(payload.span, format!("'{}' declared here", variant)),
+                                            );
+                                            Type::Unknown
+                                        },
+                                    },
+                                    None => Type::Unknown,
+                                };
+                                bindings.push((binding.clone(), field_type));
+                            }
+                        },
+                    }
+                    
+                    // Payload fields are bound in the arm's own scope
+                    let current_env = self.environment.clone();
+                    let prev_env = std::mem::replace(&mut self.environment, TypeEnvironment::extend(current_env));
+                    for (binding, field_type) in bindings {
+                        self.environment.define_at(binding, field_type, arm.span);
+                    }
+                    arm_types.push(self.check_block(&arm.body));
+                    self.environment = prev_env;
+                }
+                
+                // Every variant needs an arm unless there is an 'else' arm
+                if let Some(declared) = declared
+                    && !has_else
+                {
+                    let missing: Vec<String> = declared
+                        .variants
+                        .iter()
+                        .filter(|variant| !covered.contains(variant))
+                        .cloned()
+                        .collect();
+                    
+                    if !missing.is_empty() {
+                        self.errors.push(
+                            Diagnostic::new(
+                                TypeError::NonExhaustiveMatch {
+                                    ty: value_type.clone(),
+                                    missing,
+                                },
+                                value.span,
+                            )
+                            .with_label(declared.span, format!("{} declared here", value_type))
+                            .with_note("add an arm for each missing variant, or an 'else' arm"),
+                        );
+                    }
+                }
+                
+                // Like an if, the match has a type only when every arm agrees
+                match arm_types.split_first() {
+                    Some((first, rest)) if rest.iter().all(|ty| ty == first) => Ok(first.clone()),
+                    _ => Ok(Type::Void),
+                }
+            },
+            
+            StatementKind::WhileStatement { condition, body } => {
+                let cond_type = self.check_expression(condition)?;
+                
+                if cond_type != Type::Boolean && cond_type != Type::Unknown {
+                    self.errors.push(Diagnostic::new(
+                        TypeError::NonBooleanCondition(cond_type),
+                        condition.span,
+                    ));
+                }
+                
+                self.loop_depth += 1;
+                self.check_scoped_block(body);
+                self.loop_depth -= 1;
+                
+                Ok(Type::Void)
+            },
+            
+            StatementKind::ForStatement { variable, iterable, end, body } => {
+                let iterable_type = self.check_expression(iterable)?;
+                
+                let element_type = match end {
+                    // Both ends of a range are integers, and so is the variable
+                    Some(end) => {
+                        let end_type = self.check_expression(end)?;
+                        self.check_argument(&iterable_type, &Type::Integer, iterable)?;
+                        self.check_argument(&end_type, &Type::Integer, end)?;
+                        Type::Integer
+                    },
+                    None => match iterable_type {
+                        Type::List(element_type) => *element_type,
+                        Type::Map(key_type, _) => *key_type,
+                        Type::Unknown => Type::Unknown,
+                        other => {
+                            return Err(Diagnostic::new(TypeError::NotIterable(other), iterable.span));
+                        },
+                    },
+                };
+                
+                // The loop variable lives in the body's scope
+                let current_env = self.environment.clone();
+                let prev_env = std::mem::replace(&mut self.environment, TypeEnvironment::extend(current_env));
+                self.environment.define(variable.clone(), element_type);
+                
+                self.loop_depth += 1;
+                self.check_block(body);
+                self.loop_depth -= 1;
+                
+                self.environment = prev_env;
+                Ok(Type::Void)
+            },
+            
+            StatementKind::Break | StatementKind::Continue => {
+                if self.loop_depth == 0 {
+                    let keyword = match statement.kind {
+                        StatementKind::Break => "break",
+                        _ => "continue",
+                    };
+                    return Err(Diagnostic::new(
+                        TypeError::OutsideLoop(keyword.to_string()),
+                        statement.span,
+                    ));
+                }
+                
+                Ok(Type::Void)
+            },
+            
+            StatementKind::Return(value) => {
+                let expected = match &self.return_type {
+                    Some(ty) => ty.clone(),
+                    None => {
+                        return Err(Diagnostic::new(
+                            TypeError::ReturnOutsideFunction,
+                            statement.span,
+                        ));
+                    },
+                };
+                
+                let (found, span) = match value {
+                    Some(value) => (self.check_expression(value)?, value.span),
+                    None => (Type::Void, statement.span),
+                };
+                
+                if !self.types_compatible(&found, &expected) {
+                    return Err(Diagnostic::new(
+                        TypeError::TypeMismatch { expected, found },
+                        span,
+                    ));
+                }
+                
+                Ok(Type::Void)
+            },
+            
+            StatementKind::Comment(_) => Ok(Type::Void),
         }
     }
     
-    fn check_expression(&mut self, expr: &Expression) -> Result<Type, String> {
-        match expr {
-            Expression::StringLiteral(_) => Ok(Type::String),
+    fn check_expression(&mut self, expr: &Expression) -> Result<Type, Diagnostic<TypeError>> {
+        match &expr.kind {
+            ExpressionKind::StringLiteral(_) => Ok(Type::String),
             
-            Expression::IntegerLiteral(_) => Ok(Type::Integer),
+            ExpressionKind::IntegerLiteral(_) => Ok(Type::Integer),
+            // Already reported by the parser
+            ExpressionKind::Error => Ok(Type::Unknown),
+            ExpressionKind::BooleanLiteral(_) => Ok(Type::Boolean),
             
-            Expression::Identifier(name) => {
+            ExpressionKind::Identifier(name) => {
                 match self.environment.get(name) {
                     Some(ty) => Ok(ty),
-                    None => Err(format!("Undefined variable '{}'", name)),
+                    // A variant without a payload is written by its name alone
+                    None if self.variants.contains_key(name) => {
+                        let (enum_name, payload) = &self.variants[name];
+                        if let Some((missing, _)) = payload.fields.first() {
+                            return Err(Diagnostic::new(
+                                TypeError::MissingField {
+                                    record: name.clone(),
+                                    field: missing.clone(),
+                                },
+                                expr.span,
+                            )
+                            .with_label(payload.span, format!("'{}' declared here", name)));
+                        }
+                        Ok(Type::Enum(enum_name.clone()))
+                    },
+                    None => Err(Diagnostic::new(
+                        TypeError::UndefinedVariable(name.clone()),
+                        expr.span,
+                    )),
                 }
             },
             
-            Expression::FunctionCall { name, arguments } => {
+            ExpressionKind::FunctionCall { name, arguments } => {
                 // Check if function exists
                 let func_type = match self.environment.get(name) {
                     Some(ty) => ty,
-                    None => return Err(format!("Undefined function '{}'", name)),
+                    None => {
+                        return Err(Diagnostic::new(
+                            TypeError::UndefinedFunction(name.clone()),
+                            expr.span,
+                        ));
+                    },
                 };
                 
-                // Special case for built-in 'print' function
-                if name == "print" {
-                    // Check all arguments
-                    for arg in arguments {
-                        self.check_expression(arg)?;
-                    }
-                    return Ok(Type::Void);
-                }
-                
                 // Special case for 'function' function
                 if name == "function" {
                     if let Some(arg) = arguments.first() {
@@ -212,75 +906,517 @@ impl TypeChecker {
                 
                 // For normal functions, check parameter types
                 match func_type {
+                    // print can take any number of arguments of any type
+                    Type::Builtin(builtin) if builtin == "print" => {
+                        for arg in arguments {
+                            self.check_expression(arg)?;
+                        }
+                        Ok(Type::Void)
+                    },
+                    
+                    Type::Builtin(builtin) => self.check_builtin_call(&builtin, arguments, expr.span),
+                    
                     Type::Function { parameters, return_type } => {
                         // Check argument count
                         if arguments.len() != parameters.len() {
-                            return Err(format!(
-                                "Function '{}' expects {} arguments, got {}",
-                                name, parameters.len(), arguments.len()
-                            ));
+                            let mut diagnostic = Diagnostic::new(
+                                TypeError::ArityMismatch {
+                                    name: name.clone(),
+                                    expected: parameters.len(),
+                                    found: arguments.len(),
+                                },
+                                expr.span,
+                            );
+                            if let Some(span) = self.environment.get_span(name) {
+                                diagnostic = diagnostic.with_label(
+                                    span,
+                                    format!(
+                                        "'{}' declared here with {} parameter{}",
+                                        name,
+                                        parameters.len(),
+                                        if parameters.len() == 1 { "" } else { "s" }
+                                    ),
+                                );
+                            }
+                            return Err(diagnostic);
                         }
                         
                         // Check each argument type
                         for (arg, param_type) in arguments.iter().zip(parameters.iter()) {
                             let arg_type = self.check_expression(arg)?;
                             if !self.types_compatible(&arg_type, param_type) {
-                                return Err(format!(
-                                    "Type mismatch: expected {}, got {}",
-                                    param_type, arg_type
-                                ));
+                                let mut diagnostic = Diagnostic::new(
+                                    TypeError::TypeMismatch {
+                                        expected: param_type.clone(),
+                                        found: arg_type,
+                                    },
+                                    arg.span,
+                                );
+                                if let Some(span) = self.environment.get_span(name) {
+                                    diagnostic = diagnostic.with_label(span, format!("'{}' declared here", name));
+                                }
+                                // Keep checking the remaining arguments
+                                self.errors.push(diagnostic);
                             }
                         }
                         
                         Ok(*return_type)
                     },
-                    _ => Err(format!("'{}' is not a function", name)),
+                    _ => Err(Diagnostic::new(TypeError::NotAFunction(name.clone()), expr.span)),
                 }
             },
             
-            Expression::TypedValue { type_name, value } => {
-                let expected_type = self.parse_type_name(type_name);
+            ExpressionKind::RecordLiteral { type_name, fields } => {
+                // A variant of an enum is built the same way as a record
+                let (record, ty) = if let Some(record) = self.records.get(type_name) {
+                    (record.clone(), Type::Record(type_name.clone()))
+                } else if let Some((enum_name, payload)) = self.variants.get(type_name) {
+                    (payload.clone(), Type::Enum(enum_name.clone()))
+                } else {
+                    return Err(Diagnostic::new(TypeError::UnknownType(type_name.clone()), expr.span));
+                };
+                let declared_here = format!("'{}' declared here", type_name);
                 
-                // Special case for String[Hello] and similar constructs
-                if let Expression::Identifier(_) = &**value {
-                    return Ok(expected_type);
+                for (i, field) in fields.iter().enumerate() {
+                    if fields[..i].iter().any(|earlier| earlier.name == field.name) {
+                        return Err(Diagnostic::new(
+                            TypeError::DuplicateField {
+                                record: type_name.clone(),
+                                field: field.name.clone(),
+                            },
+                            field.span,
+                        ));
+                    }
+                    
+                    let Some((_, field_type)) = record.fields.iter().find(|(name, _)| *name == field.name) else {
+                        return Err(Diagnostic::new(
+                            TypeError::UnknownField {
+                                ty: ty.clone(),
+                                field: field.name.clone(),
+                            },
+                            field.span,
+                        )
+                        .with_label(record.span, declared_here));
+                    };
+                    
+                    let value_type = self.check_expression(&field.value)?;
+                    self.check_argument(&value_type, field_type, &field.value)?;
                 }
                 
-                // For other expressions, check their type
-                let value_type = self.check_expression(value)?;
+                // Every declared field has to be given a value
+                if let Some((missing, _)) = record
+                    .fields
+                    .iter()
+                    .find(|(name, _)| !fields.iter().any(|field| field.name == *name))
+                {
+                    return Err(Diagnostic::new(
+                        TypeError::MissingField {
+                            record: type_name.clone(),
+                            field: missing.clone(),
+                        },
+                        expr.span,
+                    )
+                    .with_label(record.span, declared_here));
+                }
                 
-                if !self.types_compatible(&value_type, &expected_type) {
-                    return Err(format!(
-                        "Type mismatch: expected {}, got {}",
-                        expected_type, value_type
+                Ok(ty)
+            },
+            
+            ExpressionKind::FieldAccess { target, field } => {
+                let target_type = self.check_expression(target)?;
+                self.field_type(&target_type, field, expr.span)
+            },
+            
+            ExpressionKind::ListLiteral(elements) => {
+                // The first element with a known type decides the element
+                // type, and every other element has to agree with it
+                let mut element_type = Type::Unknown;
+                for element in elements {
+                    let ty = self.check_expression(element)?;
+                    if !self.types_compatible(&ty, &element_type) {
+                        return Err(Diagnostic::new(
+                            TypeError::TypeMismatch {
+                                expected: element_type,
+                                found: ty,
+                            },
+                            element.span,
+                        ));
+                    }
+                    if element_type == Type::Unknown {
+                        element_type = ty;
+                    }
+                }
+                
+                Ok(Type::List(Box::new(element_type)))
+            },
+            
+            ExpressionKind::MapLiteral(entries) => {
+                // Like a list literal, the first known key and value types
+                // decide the map's type
+                let mut key_type = Type::Unknown;
+                let mut value_type = Type::Unknown;
+                for (key, value) in entries {
+                    let ty = self.check_expression(key)?;
+                    self.check_map_key(&ty, key.span)?;
+                    self.check_argument(&ty, &key_type, key)?;
+                    if key_type == Type::Unknown {
+                        key_type = ty;
+                    }
+                    
+                    let ty = self.check_expression(value)?;
+                    self.check_argument(&ty, &value_type, value)?;
+                    if value_type == Type::Unknown {
+                        value_type = ty;
+                    }
+                }
+                
+                Ok(Type::Map(Box::new(key_type), Box::new(value_type)))
+            },
+            
+            ExpressionKind::Index { target, index } => {
+                let target_type = self.check_expression(target)?;
+                let index_type = self.check_expression(index)?;
+                
+                // Maps are indexed by their key type, everything else by Integer
+                let expected_index = match &target_type {
+                    Type::Map(key_type, _) => (**key_type).clone(),
+                    _ => Type::Integer,
+                };
+                if !self.types_compatible(&index_type, &expected_index) {
+                    return Err(Diagnostic::new(
+                        TypeError::TypeMismatch {
+                            expected: expected_index,
+                            found: index_type,
+                        },
+                        index.span,
                     ));
                 }
                 
-                Ok(expected_type)
+                match target_type {
+                    Type::List(element_type) => Ok(*element_type),
+                    Type::Map(_, value_type) => Ok(*value_type),
+                    Type::Unknown => Ok(Type::Unknown),
+                    other => Err(Diagnostic::new(TypeError::NotIndexable(other), target.span)),
+                }
+            },
+            
+            ExpressionKind::UnaryOperation { operator, operand } => {
+                let operand_type = self.check_expression(operand)?;
+                let result_type = if operator == "not" { Type::Boolean } else { Type::Integer };
+                self.check_operand(operator, &result_type, &operand_type, operand)?;
+                Ok(result_type)
             },
             
-            Expression::BinaryOperation { left, operator, right } => {
-                let _left_type = self.check_expression(left)?;
-                let _right_type = self.check_expression(right)?;
+            ExpressionKind::BinaryOperation { left, operator, right } => {
+                let left_type = self.check_expression(left)?;
+                let right_type = self.check_expression(right)?;
                 
                 match operator.as_str() {
                     "is" | "is not" => {
                         // Any type can be compared for equality/inequality
                         Ok(Type::Boolean)
                     },
-                    _ => Err(format!("Unknown operator: {}", operator)),
+                    "+" | "-" | "*" | "/" | "%" => {
+                        self.check_operand(operator, &Type::Integer, &left_type, left)?;
+                        self.check_operand(operator, &Type::Integer, &right_type, right)?;
+                        Ok(Type::Integer)
+                    },
+                    "<" | "<=" | ">" | ">=" => {
+                        // Integers and strings are ordered, as long as both
+                        // sides are the same kind
+                        if !matches!(left_type, Type::Integer | Type::String | Type::Unknown) {
+                            return Err(Diagnostic::new(
+                                TypeError::InvalidOperand {
+                                    operator: operator.clone(),
+                                    expected: "Integer or String".to_string(),
+                                    found: left_type,
+                                },
+                                left.span,
+                            ));
+                        }
+                        if !self.types_compatible(&right_type, &left_type) {
+                            return Err(Diagnostic::new(
+                                TypeError::TypeMismatch {
+                                    expected: left_type,
+                                    found: right_type,
+                                },
+                                right.span,
+                            ));
+                        }
+                        Ok(Type::Boolean)
+                    },
+                    "and" | "or" => {
+                        self.check_operand(operator, &Type::Boolean, &left_type, left)?;
+                        self.check_operand(operator, &Type::Boolean, &right_type, right)?;
+                        Ok(Type::Boolean)
+                    },
+                    _ => Err(Diagnostic::new(
+                        TypeError::UnknownOperator(operator.clone()),
+                        expr.span,
+                    )),
                 }
             },
         }
     }
     
-    fn parse_type_name(&self, name: &str) -> Type {
+    // Checks a call to one of the list or map built-ins, whose result type
+    // follows from the type of the collection passed in
+    fn check_builtin_call(
+        &mut self,
+        name: &str,
+        arguments: &[Expression],
+        span: Span,
+    ) -> Result<Type, Diagnostic<TypeError>> {
+        let expected = BUILTINS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map_or(0, |(_, arity)| *arity);
+        if arguments.len() != expected {
+            return Err(Diagnostic::new(
+                TypeError::ArityMismatch {
+                    name: name.to_string(),
+                    expected,
+                    found: arguments.len(),
+                },
+                span,
+            ));
+        }
+        
+        let mut types = Vec::new();
+        for argument in arguments {
+            types.push(self.check_expression(argument)?);
+        }
+        
+        let invalid_argument = |expected: &str| {
+            Diagnostic::new(
+                TypeError::InvalidArgument {
+                    name: name.to_string(),
+                    expected: expected.to_string(),
+                    found: types[0].clone(),
+                },
+                arguments[0].span,
+            )
+        };
+        
         match name {
-            "String" => Type::String,
-            "Integer" => Type::Integer,
-            "Boolean" => Type::Boolean,
-            "Unknown" => Type::Unknown,
-            _ => Type::Unknown,
+            "length" => match &types[0] {
+                Type::List(_) | Type::Map(..) | Type::String | Type::Unknown => Ok(Type::Integer),
+                _ => Err(invalid_argument("a List, Map or String")),
+            },
+            "push" | "slice" => {
+                let element_type = match &types[0] {
+                    Type::List(element_type) => (**element_type).clone(),
+                    Type::Unknown => Type::Unknown,
+                    _ => return Err(invalid_argument("a List")),
+                };
+                
+                // push(list, value) needs a value of the element type, and
+                // slice(list, start, end) needs Integer bounds
+                if name == "push" {
+                    self.check_argument(&types[1], &element_type, &arguments[1])?;
+                    
+                    // Pushing onto a list of unknown elements tells us what it holds
+                    if element_type == Type::Unknown {
+                        return Ok(Type::List(Box::new(types[1].clone())));
+                    }
+                } else {
+                    self.check_argument(&types[1], &Type::Integer, &arguments[1])?;
+                    self.check_argument(&types[2], &Type::Integer, &arguments[2])?;
+                }
+                
+                Ok(Type::List(Box::new(element_type)))
+            },
+            _ => {
+                let (key_type, value_type) = match &types[0] {
+                    Type::Map(key_type, value_type) => ((**key_type).clone(), (**value_type).clone()),
+                    Type::Unknown => (Type::Unknown, Type::Unknown),
+                    _ => return Err(invalid_argument("a Map")),
+                };
+                
+                if matches!(name, "get" | "has" | "remove" | "insert") {
+                    self.check_argument(&types[1], &key_type, &arguments[1])?;
+                }
+                
+                match name {
+                    // A missing key gives back nothing, so the result can't be
+                    // relied on to have the value type
+                    "get" => Ok(Type::Unknown),
+                    "has" => Ok(Type::Boolean),
+                    "keys" => Ok(Type::List(Box::new(key_type))),
+                    "values" => Ok(Type::List(Box::new(value_type))),
+                    "insert" => {
+                        self.check_map_key(&types[1], arguments[1].span)?;
+                        self.check_argument(&types[2], &value_type, &arguments[2])?;
+                        
+                        // Inserting into an empty map tells us what it holds
+                        Ok(Type::Map(
+                            Box::new(if key_type == Type::Unknown { types[1].clone() } else { key_type }),
+                            Box::new(if value_type == Type::Unknown { types[2].clone() } else { value_type }),
+                        ))
+                    },
+                    _ => Ok(Type::Map(Box::new(key_type), Box::new(value_type))),
+                }
+            },
+        }
+    }
+    
+    // Collects declared fields, reporting any declared more than once
+    fn record_type(&mut self, name: &str, fields: &[Parameter], span: Span) -> RecordType {
+        let mut record = RecordType {
+            fields: Vec::new(),
+            span,
+        };
+        
+        for field in fields {
+            if record.fields.iter().any(|(existing, _)| *existing == field.name) {
+                self.errors.push(Diagnostic::new(
+                    TypeError::DuplicateField {
+                        record: name.to_string(),
+                        field: field.name.clone(),
+                    },
+                    field.span,
+                ));
+                continue;
+            }
+            let field_type = self.resolve_type(&field.type_name);
+            record.fields.push((field.name.clone(), field_type));
+        }
+        
+        record
+    }
+    
+    // The type of `field` on a value of type `ty`
+    // Record types, enums and variants share one namespace, since a record
+    // literal can name either a record type or a variant
+    fn check_name_free(&self, name: &str, span: Span) -> Result<(), Diagnostic<TypeError>> {
+        let declared = self.records.get(name).map(|record| record.span)
+            .or_else(|| self.enums.get(name).map(|declared| declared.span))
+            .or_else(|| self.variants.get(name).map(|(_, payload)| payload.span));
+        
+        match declared {
+            Some(declared) => Err(Diagnostic::new(TypeError::NameAlreadyDeclared(name.to_string()), span)
+                .with_label(declared, format!("{} declared here", name))),
+            None => Ok(()),
+        }
+    }
+    
+    fn field_type(&self, ty: &Type, field: &str, span: Span) -> Result<Type, Diagnostic<TypeError>> {
+        let unknown_field = || {
+            Diagnostic::new(
+                TypeError::UnknownField {
+                    ty: ty.clone(),
+                    field: field.to_string(),
+                },
+                span,
+            )
+        };
+        
+        match ty {
+            Type::Unknown => Ok(Type::Unknown),
+            Type::Record(name) => {
+                let record = self.records.get(name).ok_or_else(unknown_field)?;
+                record
+                    .fields
+                    .iter()
+                    .find(|(name, _)| name == field)
+                    .map(|(_, field_type)| field_type.clone())
+                    .ok_or_else(|| unknown_field().with_label(record.span, format!("'{}' declared here", name)))
+            },
+            _ => Err(unknown_field()),
+        }
+    }
+    
+    fn check_argument(&self, ty: &Type, expected: &Type, argument: &Expression) -> Result<(), Diagnostic<TypeError>> {
+        if self.types_compatible(ty, expected) {
+            Ok(())
+        } else {
+            Err(Diagnostic::new(
+                TypeError::TypeMismatch {
+                    expected: expected.clone(),
+                    found: ty.clone(),
+                },
+                argument.span,
+            ))
+        }
+    }
+    
+    // Only strings and integers can be used as map keys
+    fn check_map_key(&self, ty: &Type, span: Span) -> Result<(), Diagnostic<TypeError>> {
+        match ty {
+            Type::String | Type::Integer | Type::Unknown => Ok(()),
+            other => Err(Diagnostic::new(TypeError::InvalidMapKey(other.clone()), span)),
+        }
+    }
+    
+    fn check_operand(
+        &self,
+        operator: &str,
+        expected: &Type,
+        ty: &Type,
+        operand: &Expression,
+    ) -> Result<(), Diagnostic<TypeError>> {
+        if self.types_compatible(ty, expected) {
+            Ok(())
+        } else {
+            Err(Diagnostic::new(
+                TypeError::InvalidOperand {
+                    operator: operator.to_string(),
+                    expected: expected.to_string(),
+                    found: ty.clone(),
+                },
+                operand.span,
+            ))
+        }
+    }
+    
+    // Whether running these statements is guaranteed to hit a 'return'
+    fn always_returns(statements: &[Statement]) -> bool {
+        statements.iter().any(|statement| match &statement.kind {
+            StatementKind::Return(_) => true,
+            // An if only guarantees a return when every branch does
+            StatementKind::IfStatement { body, else_body: Some(else_body), .. } => {
+                Self::always_returns(body) && Self::always_returns(else_body)
+            },
+            // A match that isn't exhaustive is reported on its own, so here
+            // it only matters that every arm returns
+            StatementKind::Match { arms, .. } => {
+                !arms.is_empty() && arms.iter().all(|arm| Self::always_returns(&arm.body))
+            },
+            _ => false,
+        })
+    }
+    
+    // The type an annotation stands for. Problems with it are recorded and
+    // checking carries on with what could be made of it.
+    fn resolve_type(&mut self, type_name: &TypeName) -> Type {
+        match &type_name.kind {
+            TypeNameKind::String => Type::String,
+            TypeNameKind::Integer => Type::Integer,
+            TypeNameKind::Boolean => Type::Boolean,
+            TypeNameKind::Unknown => Type::Unknown,
+            TypeNameKind::List(element_type) => Type::List(Box::new(self.resolve_type(element_type))),
+            TypeNameKind::Map(key_type, value_type) => {
+                let key = self.resolve_type(key_type);
+                if let Err(e) = self.check_map_key(&key, key_type.span) {
+                    self.errors.push(e);
+                }
+                Type::Map(Box::new(key), Box::new(self.resolve_type(value_type)))
+            },
+            TypeNameKind::Named(name) => {
+                if self.records.contains_key(name) {
+                    return Type::Record(name.clone());
+                }
+                
+                if self.enums.contains_key(name) {
+                    return Type::Enum(name.clone());
+                }
+                
+                self.errors.push(
+                    Diagnostic::new(TypeError::UnknownType(name.clone()), type_name.span)
+                        .with_note("types are declared with 'type' or 'enum' before they are used"),
+                );
+                Type::Unknown
+            },
         }
     }
     
@@ -291,6 +1427,13 @@ impl TypeChecker {
         }
         
         // Otherwise, types must match exactly
-        actual == expected
+        match (actual, expected) {
+            (Type::List(actual), Type::List(expected)) => self.types_compatible(actual, expected),
+            (Type::Map(actual_key, actual_value), Type::Map(expected_key, expected_value)) => {
+                self.types_compatible(actual_key, expected_key)
+                    && self.types_compatible(actual_value, expected_value)
+            },
+            _ => actual == expected,
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,769 @@
+// Runs whole scripts through the noam binary and checks what they print
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+struct Output {
+    stdout: String,
+    stderr: String,
+    success: bool,
+}
+
+fn run(args: &[&str], source: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_noam"))
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start noam");
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(source.as_bytes())
+        .expect("failed to write the script");
+
+    let output = child.wait_with_output().expect("failed to wait for noam");
+    Output {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        success: output.status.success(),
+    }
+}
+
+// Runs a script that is expected to succeed, returning what it printed
+fn run_ok(source: &str) -> String {
+    let output = run(&[], source);
+    assert!(output.success, "script failed:\n{}", output.stderr);
+    output.stdout
+}
+
+// Runs a script that is expected to fail, returning the rendered errors
+fn run_err(args: &[&str], source: &str) -> String {
+    let output = run(args, source);
+    assert!(!output.success, "script succeeded:\n{}", output.stdout);
+    output.stderr
+}
+
+// Built-ins
+
+#[test]
+fn user_function_shadows_list_builtin() {
+    let source = "
+func length(s: String): Integer {
+    return 42
+}
+print(length(String[abc]))
+";
+    assert_eq!(run_ok(source), "42\n");
+}
+
+#[test]
+fn builtins_still_work_without_shadowing() {
+    let source = "
+let items = push(List[1, 2], 3)
+print(length(items))
+print(slice(items, 1, 3))
+";
+    assert_eq!(run_ok(source), "3\n[2, 3]\n");
+}
+
+#[test]
+fn builtin_cannot_be_assigned_to() {
+    let stderr = run_err(&["check"], "length = 1\n");
+    assert!(stderr.contains("E0308"), "{}", stderr);
+}
+
+#[test]
+fn user_function_shadows_map_builtin() {
+    let source = "
+func get(a: Integer): Integer {
+    return a + 1
+}
+func keys(m: Map[String, Integer]): String {
+    return String[mine]
+}
+print(get(1))
+print(keys(Map[String[a]: 1]))
+";
+    assert_eq!(run_ok(source), "2\nmine\n");
+}
+
+#[test]
+fn map_builtins_still_work_without_shadowing() {
+    let source = "
+let ages = insert(Map[String[ann]: 30], String[bob], 25)
+print(get(ages, String[bob]))
+print(has(ages, String[cy]))
+print(keys(remove(ages, String[ann])))
+print(values(ages))
+";
+    assert_eq!(run_ok(source), "25\nfalse\n[bob]\n[30, 25]\n");
+}
+
+// Scoping
+
+#[test]
+fn function_assigns_the_variable_it_was_declared_next_to() {
+    let source = "
+let x = 1
+func f() {
+    x = 2
+}
+func h() {
+    let x = String[local]
+    f()
+    print(x)
+}
+h()
+print(x)
+";
+    assert_eq!(run_ok(source), "local\n2\n");
+}
+
+#[test]
+fn function_cannot_see_the_callers_locals() {
+    let source = "
+func f() {
+    print(y)
+}
+func h() {
+    let y = 1
+    f()
+}
+h()
+";
+    let stderr = run_err(&[], source);
+    assert!(stderr.contains("Undefined variable 'y'"), "{}", stderr);
+}
+
+#[test]
+fn recursive_and_nested_functions() {
+    let source = "
+func fact(n: Integer): Integer {
+    if n <= 1 {
+        return 1
+    }
+    return n * fact(n - 1)
+}
+func outer(): Integer {
+    let base = 10
+    func inner(a: Integer): Integer {
+        return base + a
+    }
+    return inner(5)
+}
+print(fact(10))
+print(outer())
+";
+    assert_eq!(run_ok(source), "3628800\n15\n");
+}
+
+#[test]
+fn block_scopes_end_with_their_block() {
+    let source = "
+let total = 0
+for i in 0 to 4 {
+    let step = i * 2
+    total = total + step
+}
+if total > 0 {
+    let total = String[shadowed]
+    print(total)
+}
+print(total)
+";
+    assert_eq!(run_ok(source), "shadowed\n12\n");
+}
+
+// Comments
+
+#[test]
+fn doc_comment_without_a_function_is_a_comment() {
+    let source = "
+/// not attached to anything
+let a = 1
+/// Says hello
+// a plain comment in between
+func hello() {
+    print(String[hello])
+}
+hello()
+print(a)
+";
+    assert_eq!(run_ok(source), "hello\n1\n");
+}
+
+// Error recovery
+
+// The error codes reported, in order
+fn error_codes(stderr: &str) -> Vec<&str> {
+    stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("error[")?.split(']').next())
+        .collect()
+}
+
+#[test]
+fn lex_error_does_not_cascade_into_the_next_line() {
+    let source = "
+let n = Integer[0x_FF]
+let m = 2
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0104"], "{}", stderr);
+    assert!(stderr.contains("2:9"), "{}", stderr);
+}
+
+#[test]
+fn lex_errors_are_reported_where_they_happen() {
+    let source = "
+print(1 @ 2)
+func f(@) {
+}
+let z = 3 @
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0106", "E0106", "E0106"], "{}", stderr);
+}
+
+#[test]
+fn parse_errors_in_separate_statements_are_all_reported() {
+    let source = "
+let = 1
+print(String[fine])
+func (a: Integer) {
+}
+if {
+}
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0201", "E0201", "E0202"], "{}", stderr);
+}
+
+#[test]
+fn recovery_resumes_at_the_next_line() {
+    let source = "
+let = 1
+print(1 +)
+func (a) {}
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0201", "E0202", "E0201"], "{}", stderr);
+    assert!(stderr.contains("<stdin>:3:10"), "{}", stderr);
+}
+
+#[test]
+fn error_at_the_start_of_a_line_is_skipped() {
+    let source = "
+)
+print(1)
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0202"], "{}", stderr);
+}
+
+// Line breaks
+
+#[test]
+fn operator_starting_a_line_starts_a_new_statement() {
+    let source = "
+let a = 5
+let b = a
+-1
+print(b)
+";
+    assert_eq!(run_ok(source), "5\n");
+}
+
+#[test]
+fn operator_ending_a_line_continues_the_expression() {
+    let source = "
+let a = 5
+let c = a +
+    2 *
+    3
+print(c)
+";
+    assert_eq!(run_ok(source), "11\n");
+}
+
+#[test]
+fn operator_starting_a_line_after_an_expression_is_an_error() {
+    let source = "
+let d = 5
+    * 2
+";
+    let stderr = run_err(&["check"], source);
+    assert!(stderr.contains("move the operator to the end of the previous line"), "{}", stderr);
+}
+
+// Type annotations
+
+#[test]
+fn nested_collection_annotations_are_checked() {
+    let ok = "
+let nested: Map[String, Map[String, List[Integer]]] = Map[String[a]: Map[String[b]: List[1, 2]]]
+print(nested)
+";
+    assert_eq!(run_ok(ok), "{a: {b: [1, 2]}}\n");
+
+    let mismatch = "
+let nested: Map[String, Map[String, Integer]] = Map[String[a]: Map[String[b]: String[x]]]
+";
+    let stderr = run_err(&["check"], mismatch);
+    assert!(
+        stderr.contains("expected Map[String, Map[String, Integer]], got Map[String, Map[String, String]]"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn map_key_rules_apply_to_annotations() {
+    let source = "
+func f(m: Map[Boolean, Integer]) {
+}
+let g: Map[Map[String, Integer], Integer] = Map[]
+";
+    let stderr = run_err(&["check"], source);
+    assert!(stderr.contains("Map keys must be String or Integer, got Boolean"), "{}", stderr);
+    assert!(stderr.contains("Map keys must be String or Integer, got Map[String, Integer]"), "{}", stderr);
+}
+
+#[test]
+fn mismatched_annotation_still_declares_the_variable() {
+    let source = "
+enum Shape { Circle[r: Integer], Empty }
+let s: Shape = 5
+print(s)
+let n: Integer = s
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0305", "E0305"], "{}", stderr);
+    assert!(stderr.contains("expected Shape, got Integer"), "{}", stderr);
+    assert!(stderr.contains("expected Integer, got Shape"), "{}", stderr);
+}
+
+#[test]
+fn unknown_type_names_are_reported() {
+    let source = "
+func f(p: Pointt): Integer {
+    return p.x
+}
+f(Integer[5])
+let a: Strin = String[a]
+func g(): Lisst {
+}
+type Line { next: Line, bad: List[Nope] }
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0317", "E0317", "E0317", "E0317"], "{}", stderr);
+    for name in ["Pointt", "Strin", "Lisst", "Nope"] {
+        assert!(stderr.contains(&format!("Unknown type '{}'", name)), "{}", stderr);
+    }
+}
+
+#[test]
+fn records_can_refer_to_themselves_and_unknown_stays_allowed() {
+    let source = "
+type Node { value: Integer, children: List[Node] }
+let leaf = Node[value: 2, children: List[]]
+let root = Node[value: 1, children: List[leaf]]
+let anything: Unknown = 3
+print(root.children[0].value)
+print(anything)
+";
+    assert_eq!(run_ok(source), "2\n3\n");
+}
+
+// Enums and match
+
+const SHAPE: &str = "
+enum Shape {
+    Circle[radius: Integer],
+    Rect[width: Integer, height: Integer],
+    Empty,
+}
+";
+
+#[test]
+fn match_binds_payload_fields() {
+    let source = SHAPE.to_string()
+        + "
+func area(shape: Shape): Integer {
+    match shape {
+        Circle[radius] { return 3 * radius * radius }
+        Rect[width: w, height: h] { return w * h }
+        Empty { return 0 }
+    }
+}
+for shape in List[Circle[radius: 2], Rect[height: 3, width: 4], Empty] {
+    print(shape)
+    print(area(shape))
+}
+";
+    assert_eq!(
+        run_ok(&source),
+        "Circle { radius: 2 }\n12\nRect { width: 4, height: 3 }\n12\nEmpty\n0\n"
+    );
+}
+
+#[test]
+fn match_bindings_live_in_the_arm_scope() {
+    let source = SHAPE.to_string()
+        + "
+let radius = 100
+match Circle[radius: 1] {
+    Circle[radius] { print(radius) }
+    else { print(String[other]) }
+}
+print(radius)
+";
+    assert_eq!(run_ok(&source), "1\n100\n");
+}
+
+#[test]
+fn non_exhaustive_match_lists_the_missing_variants() {
+    let source = SHAPE.to_string()
+        + "
+let shape: Shape = Empty
+match shape {
+    Circle[radius] { print(radius) }
+}
+";
+    let stderr = run_err(&["check"], &source);
+    assert_eq!(error_codes(&stderr), vec!["E0322"], "{}", stderr);
+    assert!(stderr.contains("Match on Shape doesn't cover 'Rect', 'Empty'"), "{}", stderr);
+}
+
+#[test]
+fn else_arm_makes_a_match_exhaustive() {
+    let source = SHAPE.to_string()
+        + "
+match Rect[width: 1, height: 2] {
+    Circle { print(String[circle]) }
+    else { print(String[something else]) }
+}
+";
+    assert_eq!(run_ok(&source), "something else\n");
+}
+
+#[test]
+fn match_arm_errors() {
+    let source = SHAPE.to_string()
+        + "
+enum Color { Red, Green }
+let shape: Shape = Empty
+match shape {
+    Circle[diameter] { print(diameter) }
+    Red { print(1) }
+    Empty {}
+    Empty {}
+    else {}
+}
+match 3 {
+    else {}
+}
+";
+    let stderr = run_err(&["check"], &source);
+    assert_eq!(error_codes(&stderr), vec!["E0318", "E0323", "E0324", "E0321"], "{}", stderr);
+}
+
+#[test]
+fn variant_names_belong_to_one_enum() {
+    let source = "
+enum A { None, Some[v: Integer] }
+enum B { None, Other }
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0325"], "{}", stderr);
+    assert!(stderr.contains("Variant 'None' is already declared by A"), "{}", stderr);
+}
+
+#[test]
+fn variant_named_like_a_record_type() {
+    let source = "
+type Circle { r: Integer }
+enum Shape { Circle[r: Integer], Empty }
+let c = Circle[r: 1]
+print(c.r)
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0326"], "{}", stderr);
+    assert!(stderr.contains("'Circle' is already declared"), "{}", stderr);
+}
+
+#[test]
+fn enum_named_like_a_record_type() {
+    let source = "
+type Shape { sides: Integer }
+enum Shape { Circle, Empty }
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0326"], "{}", stderr);
+}
+
+#[test]
+fn record_type_named_like_an_enum() {
+    let source = "
+enum Shape { Circle, Empty }
+type Shape { sides: Integer }
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0326"], "{}", stderr);
+}
+
+#[test]
+fn record_type_named_like_a_variant() {
+    let source = "
+enum Shape { Circle, Empty }
+type Circle { r: Integer }
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0326"], "{}", stderr);
+}
+
+#[test]
+fn bare_and_payload_variants_as_arguments() {
+    let source = "
+enum A { None, Some[v: Integer] }
+func f(a: A) {
+    match a {
+        None { print(0) }
+        Some[v] { print(v) }
+    }
+}
+f(None)
+f(Some[v: 5])
+";
+    assert_eq!(run_ok(source), "0\n5\n");
+}
+
+// Loops
+
+#[test]
+fn break_and_continue_inside_an_if_reach_the_loop() {
+    let source = "
+let i = 0
+while True {
+    i = i + 1
+    if i is 2 {
+        continue
+    }
+    if i > 3 {
+        if i is 4 {
+            break
+        }
+    }
+    print(i)
+}
+for j in 0 to 5 {
+    if j is 1 {
+        continue
+    } else {
+        if j is 3 {
+            break
+        }
+    }
+    print(j)
+}
+";
+    assert_eq!(run_ok(source), "1\n3\n0\n2\n");
+}
+
+#[test]
+fn break_inside_a_function_inside_a_loop() {
+    let source = "
+while True {
+    func f() {
+        break
+    }
+    func g() {
+        if True {
+            continue
+        }
+    }
+    break
+}
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0311", "E0311"], "{}", stderr);
+}
+
+// Returns
+
+#[test]
+fn if_without_else_may_not_return() {
+    let source = "
+func sign(n: Integer): Integer {
+    if n < 0 {
+        return -1
+    }
+}
+";
+    let stderr = run_err(&["check"], source);
+    assert_eq!(error_codes(&stderr), vec!["E0310"], "{}", stderr);
+}
+
+#[test]
+fn returns_on_every_path() {
+    let source = SHAPE.to_string()
+        + "
+func sign(n: Integer): Integer {
+    if n < 0 {
+        return -1
+    } else {
+        if n is 0 {
+            return 0
+        }
+        return 1
+    }
+}
+func size(s: Shape): Integer {
+    match s {
+        Circle[radius] { return radius }
+        Rect[width, height] { return width * height }
+        Empty { return 0 }
+    }
+}
+print(sign(-5))
+print(sign(0))
+print(size(Rect[width: 2, height: 3]))
+";
+    assert_eq!(run_ok(&source), "-1\n0\n6\n");
+}
+
+#[test]
+fn match_arm_without_a_return() {
+    let source = SHAPE.to_string()
+        + "
+func size(s: Shape): Integer {
+    match s {
+        Circle[radius] { return radius }
+        Rect[width, height] { print(width) }
+        Empty { return 0 }
+    }
+}
+";
+    let stderr = run_err(&["check"], &source);
+    assert_eq!(error_codes(&stderr), vec!["E0310"], "{}", stderr);
+}
+
+#[test]
+fn return_leaves_loops_and_blocks_at_once() {
+    let source = "
+func first_over(limit: Integer): Integer {
+    for i in 0 to 100 {
+        while True {
+            if i * i > limit {
+                return i
+            }
+            break
+        }
+    }
+    return -1
+}
+print(first_over(10))
+print(first_over(100000))
+";
+    assert_eq!(run_ok(source), "4\n-1\n");
+}
+
+// Recursion
+
+const COUNT_DOWN: &str = "
+func f(n: Integer): Integer {
+    if n is 0 {
+        return 0
+    }
+    return 1 + f(n - 1)
+}
+";
+
+#[test]
+fn deep_recursion_within_the_limit() {
+    let source = COUNT_DOWN.to_string() + "print(f(900))\n";
+    assert_eq!(run_ok(&source), "900\n");
+}
+
+#[test]
+fn runaway_recursion_is_a_runtime_error() {
+    let source = COUNT_DOWN.to_string() + "print(f(100000))\n";
+    let stderr = run_err(&[], &source);
+    assert_eq!(error_codes(&stderr), vec!["E0416"], "{}", stderr);
+}
+
+// Operators
+
+#[test]
+fn and_or_short_circuit() {
+    let source = "
+func loud(): Boolean {
+    print(String[evaluated])
+    return True
+}
+print(False and loud())
+print(True or loud())
+print(False and 1 / 0 is 0)
+print(True or 1 / 0 is 0)
+print(True and loud())
+";
+    assert_eq!(run_ok(source), "false\ntrue\nfalse\ntrue\nevaluated\ntrue\n");
+}
+
+#[test]
+fn not_binds_looser_than_is_and_tighter_than_and() {
+    let source = "
+print(not 1 is 2)
+print(not 1 is not 2)
+print(not True and False)
+print(not False or True)
+";
+    assert_eq!(run_ok(source), "true\nfalse\nfalse\ntrue\n");
+}
+
+#[test]
+fn arithmetic_precedence_and_associativity() {
+    let source = "
+print(1 + 2 * 3)
+print((1 + 2) * 3)
+print(10 - 2 - 3)
+print(20 / 2 / 5)
+print(7 % 3 * 2)
+print(1 + 2 < 2 * 2)
+";
+    assert_eq!(run_ok(source), "7\n9\n5\n2\n2\ntrue\n");
+}
+
+#[test]
+fn unary_minus() {
+    let source = "
+let a = 4
+print(-2 * 3)
+print(2 - -3)
+print(- -a)
+print(-(1 + a))
+";
+    assert_eq!(run_ok(source), "-6\n5\n4\n-5\n");
+}
+
+#[test]
+fn integer_overflow_is_a_runtime_error() {
+    for expression in ["big + 1", "big * 2", "-big - 2"] {
+        let source = format!("let big = 9223372036854775807\nprint({})\n", expression);
+        let stderr = run_err(&[], &source);
+        assert_eq!(error_codes(&stderr), vec!["E0409"], "{}: {}", expression, stderr);
+    }
+}
+
+#[test]
+fn division_by_zero_is_a_runtime_error() {
+    for expression in ["5 / zero", "5 % zero"] {
+        let source = format!("let zero = 0\nprint({})\n", expression);
+        let stderr = run_err(&[], &source);
+        assert_eq!(error_codes(&stderr), vec!["E0408"], "{}: {}", expression, stderr);
+    }
+}